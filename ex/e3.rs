@@ -9,8 +9,8 @@ fn main() -> Result<(), TempError> {
 
     // Create a read-only memory mapping.
     #[cfg(feature = "mmap_support")]
-    unsafe {
-        let mmap = temp_file.mmap()?;
+    {
+        let mmap = temp_file.map()?;
         let content = std::str::from_utf8(&mmap)
             .unwrap_or("Invalid UTF-8 sequence");
         println!("Memory-mapped content: {content}");
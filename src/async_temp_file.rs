@@ -0,0 +1,401 @@
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::cleanup::{remove_dir_and_parents, remove_file_and_parents, CleanupReport};
+use crate::config::TempConfig;
+use crate::error::{IoResultExt, Operation, TempError, TempResult};
+use crate::helpers::{normalize_path, MoveKind};
+use crate::temp_dir::TempDir;
+use crate::temp_file::{PersistMode, TempFile};
+
+/// Runs blocking filesystem work on tokio's blocking thread pool.
+pub(crate) async fn blocking<T, F>(f: F) -> TempResult<T>
+where
+    F: FnOnce() -> TempResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| TempError::IO(io::Error::other(e)))?
+}
+
+/// Removes a temporary file, and any parent directories created for it which are left empty, without waiting for the result.
+///
+/// Inside a tokio runtime the removal is moved to the blocking thread pool so that `Drop` never stalls the runtime.
+pub(crate) fn remove_detached(path: PathBuf, created_parent: Option<PathBuf>) {
+    let remove = move || {
+        if let Err((target, e)) = remove_file_and_parents(&path, created_parent.as_deref()) {
+            crate::cleanup::report_drop_failure(&target, &e);
+        }
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(remove);
+        }
+        Err(_) => remove(),
+    }
+}
+
+/// An asynchronous temporary file for use with tokio, which is automatically deleted when dropped unless persisted.
+///
+/// Dropping an armed `AsyncTempFile` inside a runtime deletes the file on the blocking thread pool without waiting for it.
+/// Use [`cleanup`](AsyncTempFile::cleanup) to delete it and observe the result.
+#[derive(Debug)]
+pub struct AsyncTempFile {
+    /// The full path to the temporary file.
+    path: Option<PathBuf>,
+    /// The underlying file handle.
+    file: Option<File>,
+    /// Directories created to hold the temporary file that did not exist.
+    created_parent: Option<PathBuf>,
+    /// The entry recording the file in the process-wide registry, if it is enabled.
+    registry_id: Option<usize>,
+}
+
+impl AsyncTempFile {
+    /// Creates a new temporary file at the specified path.
+    ///
+    /// The current `TempConfig` of the calling thread applies, even though the file is created on the blocking thread pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path at which to create the file. If a relative path is provided, it is resolved relative to the base directory of the current `TempConfig`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created.
+    pub async fn new<P: AsRef<Path>>(path: P) -> TempResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let config = TempConfig::current();
+        let file = blocking(move || {
+            let _scope = config.scoped();
+            TempFile::new(path)
+        })
+        .await?;
+        Ok(Self::from_temp_file(file))
+    }
+
+    /// Creates a new temporary file with a random name in the given directory.
+    ///
+    /// The current `TempConfig` of the calling thread applies, even though the file is created on the blocking thread pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory in which to create the file. If `None`, the base directory of the current `TempConfig` is used. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a unique filename cannot be generated or if file creation fails.
+    pub async fn new_random<P: AsRef<Path>>(dir: Option<P>) -> TempResult<Self> {
+        let dir = dir.map(|d| d.as_ref().to_path_buf());
+        let config = TempConfig::current();
+        let file = blocking(move || {
+            let _scope = config.scoped();
+            TempFile::new_random(dir)
+        })
+        .await?;
+        Ok(Self::from_temp_file(file))
+    }
+
+    /// Converts a `TempFile` into an `AsyncTempFile`, taking over responsibility for deleting it.
+    #[must_use]
+    pub fn from_temp_file(file: TempFile) -> Self {
+        let (path, file, created_parent, registry_id) = file.into_parts();
+        Self {
+            path,
+            file: file.map(File::from_std),
+            created_parent,
+            registry_id,
+        }
+    }
+
+    /// Clears the path, disarming deletion, and removes the file from the process-wide registry.
+    fn forget_path(&mut self) -> Option<PathBuf> {
+        if let Some(id) = self.registry_id.take() {
+            crate::registry::unregister(id);
+        }
+        self.path.take()
+    }
+
+    /// Returns the path to the temporary file.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Checks if the file is still active.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Returns an immutable reference to the file handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available.
+    pub fn file(&self) -> TempResult<&File> {
+        self.file.as_ref().ok_or(TempError::FileIsNone)
+    }
+
+    /// Returns a mutable reference to the file handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available.
+    pub fn file_mut(&mut self) -> TempResult<&mut File> {
+        self.file.as_mut().ok_or(TempError::FileIsNone)
+    }
+
+    /// Synchronizes the file's state with the storage device.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or if syncing fails.
+    pub async fn sync_all(&self) -> TempResult<()> {
+        self.file()?
+            .sync_all()
+            .await
+            .context(Operation::Sync, &self.path.clone().unwrap_or_default())
+    }
+
+    /// Atomically renames the temporary file, replacing any existing file at the new path.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_path` - The new path for the file. If `new_path` is a bare file name, it is placed in the old file's parent directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if moving the file fails.
+    pub async fn rename<P: AsRef<Path>>(&mut self, new_path: P) -> TempResult<()> {
+        self.rename_with(new_path, PersistMode::Overwrite).await
+    }
+
+    /// Atomically renames the temporary file, treating an existing file at the new path according to `mode`.
+    ///
+    /// See [`TempFile::rename_with`] for how files on other filesystems are handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_path` - The new path for the file. If `new_path` is a bare file name, it is placed in the old file's parent directory.
+    /// * `mode` - Whether an existing file at `new_path` may be replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::PathExists)` if `mode` is `NoClobber` and `new_path` exists, or an error if moving the file fails.
+    pub async fn rename_with<P: AsRef<Path>>(
+        &mut self,
+        new_path: P,
+        mode: PersistMode,
+    ) -> TempResult<()> {
+        let Some(old_path) = self.path.clone() else {
+            return Ok(());
+        };
+        // Writes to a tokio file complete in the background, so finish them before the contents may be copied.
+        if let Some(ref mut file) = self.file {
+            file.flush().await.context(Operation::Flush, &old_path)?;
+        }
+        let new_path = normalize_path(new_path.as_ref());
+        let new_path = if TempFile::is_bare_name(&new_path) {
+            old_path
+                .parent()
+                .ok_or_else(|| TempError::InvalidFileOrPath(old_path.clone()))?
+                .join(new_path)
+        } else {
+            new_path
+        };
+        let target = new_path.clone();
+        let kind = blocking(move || crate::helpers::move_file(&old_path, &target, mode)).await?;
+        if kind == MoveKind::Copied {
+            if let Some(ref mut old_file) = self.file {
+                let pos = old_file.stream_position().await?;
+                let mut file = tokio::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&new_path)
+                    .await
+                    .context(Operation::Open, &new_path)?;
+                file.seek(SeekFrom::Start(pos)).await?;
+                self.file = Some(file);
+            }
+        }
+        if let Some(id) = self.registry_id {
+            crate::registry::update(id, &new_path);
+        }
+        self.path = Some(new_path);
+        Ok(())
+    }
+
+    /// Converts the `AsyncTempFile` into a permanent file, returning the file handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inner file is `None`.
+    pub fn persist(&mut self) -> TempResult<File> {
+        self.forget_path();
+        self.file.take().ok_or(TempError::FileIsNone)
+    }
+
+    /// Atomically moves the temporary file to `path` and persists it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to persist the file at.
+    /// * `mode` - Whether an existing file at `path` may be replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::PathExists)` if `mode` is `NoClobber` and `path` exists, or an error if moving or persisting the file fails.
+    pub async fn persist_to<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        mode: PersistMode,
+    ) -> TempResult<File> {
+        self.rename_with(path, mode).await?;
+        self.persist()
+    }
+
+    /// Deletes the temporary file, and any parent directories created for it which are left empty, and reports any error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if removal fails.
+    pub async fn cleanup(mut self) -> TempResult<()> {
+        self.file = None;
+        let created = self.created_parent.take();
+        match self.forget_path() {
+            Some(p) => {
+                blocking(move || {
+                    remove_file_and_parents(&p, created.as_deref()).map_err(|(_, e)| e.into())
+                })
+                .await
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl AsyncRead for AsyncTempFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.file {
+            Some(ref mut file) => Pin::new(file).poll_read(cx, buf),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                TempError::FileIsNone,
+            ))),
+        }
+    }
+}
+
+impl AsyncWrite for AsyncTempFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.file {
+            Some(ref mut file) => Pin::new(file).poll_write(cx, buf),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                TempError::FileIsNone,
+            ))),
+        }
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.file {
+            Some(ref mut file) => Pin::new(file).poll_flush(cx),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                TempError::FileIsNone,
+            ))),
+        }
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.file {
+            Some(ref mut file) => Pin::new(file).poll_shutdown(cx),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                TempError::FileIsNone,
+            ))),
+        }
+    }
+}
+
+impl AsyncSeek for AsyncTempFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        match self.file {
+            Some(ref mut file) => Pin::new(file).start_seek(position),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                TempError::FileIsNone,
+            )),
+        }
+    }
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        match self.file {
+            Some(ref mut file) => Pin::new(file).poll_complete(cx),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                TempError::FileIsNone,
+            ))),
+        }
+    }
+}
+
+impl Drop for AsyncTempFile {
+    fn drop(&mut self) {
+        if let Some(p) = self.forget_path() {
+            remove_detached(p, self.created_parent.take());
+        }
+    }
+}
+
+impl TempDir {
+    /// Creates a new asynchronous temporary file with the given filename in the directory.
+    ///
+    /// The file is not tracked by the `TempDir`, but is still removed with the directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The name of the file to create. It must stay inside the directory, as with [`create_file`](TempDir::create_file).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::EscapesDirectory)` if `filename` leaves the directory, or an error if the inner path is `None` or if file creation fails.
+    pub async fn create_file_async<S: AsRef<str>>(&self, filename: S) -> TempResult<AsyncTempFile> {
+        let dir = self
+            .path()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let file_path = crate::helpers::join_within(dir, Path::new(filename.as_ref()))?;
+        AsyncTempFile::new(file_path).await
+    }
+
+    /// Removes the temporary directory and all of its contents without blocking the runtime, like [`cleanup`](TempDir::cleanup).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory itself cannot be listed.
+    pub async fn cleanup_async(mut self) -> TempResult<CleanupReport> {
+        let mut report = CleanupReport::default();
+        match self.disarm_for_cleanup() {
+            Some((path, created)) => {
+                blocking(move || {
+                    remove_dir_and_parents(&path, created.as_deref(), &mut report)?;
+                    Ok(report)
+                })
+                .await
+            }
+            None => Ok(report),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::TempConfig;
+use crate::error::{TempError, TempResult};
+use crate::helpers::normalize_path;
+use crate::location::TempLocation;
+use crate::name_gen::NameGenerator;
+use crate::temp_dir::TempDir;
+use crate::temp_file::{FileSpec, TempFile};
+
+/// Resolves the parent directory of a builder, defaulting to the directory its location resolves to.
+fn resolve_dir(dir: Option<&Path>, location: Option<&TempLocation>) -> TempResult<PathBuf> {
+    let base = || match location {
+        Some(location) => location.resolve(),
+        None => crate::config::base_dir(),
+    };
+    match dir {
+        Some(d) => {
+            let d_ref = normalize_path(d);
+            if d_ref.is_absolute() {
+                Ok(d_ref)
+            } else {
+                Ok(base()?.join(d_ref))
+            }
+        }
+        None => base(),
+    }
+}
+
+/// Repeatedly builds names of the form `{prefix}{generated}{suffix}` in `dir` and passes them to `create` until one does not already exist.
+///
+/// `create` must create the entry exclusively and report a taken name as `TempError::PathExists`, so that collisions are detected
+/// atomically rather than by checking for the name first. `retries` is the total number of names tried before giving up with
+/// `TempError::NameSpaceExhausted`; at least one is always tried, even if it is zero. If `rand_len` is zero, the single name
+/// `{prefix}{suffix}` is tried.
+pub(crate) fn create_unique<T>(
+    dir: &Path,
+    prefix: &str,
+    suffix: &str,
+    rand_len: usize,
+    retries: usize,
+    names: &dyn NameGenerator,
+    mut create: impl FnMut(PathBuf) -> TempResult<T>,
+) -> TempResult<T> {
+    let attempts = if rand_len == 0 { 1 } else { retries.max(1) };
+    for _ in 0..attempts {
+        let generated = if rand_len == 0 {
+            String::new()
+        } else {
+            names.generate(rand_len)
+        };
+        let name = format!("{prefix}{generated}{suffix}");
+        match create(dir.join(name)) {
+            Err(TempError::PathExists(path)) if rand_len == 0 => {
+                return Err(TempError::PathExists(path))
+            }
+            Err(TempError::PathExists(_)) => {}
+            result => return result,
+        }
+    }
+    Err(TempError::NameSpaceExhausted {
+        attempts,
+        dir: dir.to_path_buf(),
+    })
+}
+
+/// A builder for `TempFile`s with a custom name, permission mode, and open options.
+///
+/// Names are of the form `{prefix}{random}{suffix}`, for example `build-Xa3k.o`.
+#[derive(Debug, Clone, Default)]
+pub struct TempFileBuilder {
+    /// Text placed before the random part of the name.
+    prefix: String,
+    /// Text placed after the random part of the name, such as an extension.
+    suffix: String,
+    /// Length of the random part of the name, or `None` for the default.
+    rand_len: Option<usize>,
+    /// Permission mode of the created file, or `None` for the default.
+    mode: Option<u32>,
+    /// Whether writes always append to the end of the file.
+    append: bool,
+    /// Whether the returned handle is read-only.
+    read_only: bool,
+    /// The directory to create the file in, or `None` for the base directory of the configuration.
+    dir: Option<PathBuf>,
+    /// Where to place the file, or `None` for the base directory of the configuration.
+    location: Option<TempLocation>,
+    /// The generator of the middle part of the name, or `None` for the one of the configuration.
+    names: Option<Arc<dyn NameGenerator>>,
+    /// The configuration in effect while building, or `None` for the current one of the thread.
+    config: Option<TempConfig>,
+}
+
+impl TempFileBuilder {
+    /// Creates a new builder with an empty prefix and suffix and the default random part length.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the text placed before the random part of the name.
+    pub fn prefix<S: AsRef<str>>(&mut self, prefix: S) -> &mut Self {
+        self.prefix = prefix.as_ref().to_string();
+        self
+    }
+
+    /// Sets the text placed after the random part of the name, such as an extension.
+    pub fn suffix<S: AsRef<str>>(&mut self, suffix: S) -> &mut Self {
+        self.suffix = suffix.as_ref().to_string();
+        self
+    }
+
+    /// Sets the length of the random part of the name. A length of zero uses exactly `{prefix}{suffix}`.
+    pub fn rand_len(&mut self, len: usize) -> &mut Self {
+        self.rand_len = Some(len);
+        self
+    }
+
+    /// Sets the permission mode of the created file, subject to the umask. Defaults to the file mode of the configuration. Ignored on non-Unix platforms.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets whether writes always append to the end of the file.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Sets whether the handle of the created file is read-only.
+    pub fn read_only(&mut self, read_only: bool) -> &mut Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets the directory to create the file in. If a relative directory is provided, it is resolved relative to the location (the base directory of the configuration by default).
+    pub fn dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the storage location to create the file in, such as a RAM-backed or large disk. It is resolved each time the builder builds.
+    pub fn location(&mut self, location: TempLocation) -> &mut Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Sets the generator of the part of the name between the prefix and suffix, such as `SeededNames` for reproducible names.
+    ///
+    /// Clones of the builder share the generator, so they continue the same sequence.
+    pub fn name_generator<G: NameGenerator + 'static>(&mut self, names: G) -> &mut Self {
+        self.names = Some(Arc::new(names));
+        self
+    }
+
+    /// Sets the configuration used while building, in place of the current one of the thread, see [`TempConfig`].
+    ///
+    /// Settings made directly on the builder take precedence over it.
+    pub fn config(&mut self, config: TempConfig) -> &mut Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Creates the temporary file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the location cannot be resolved, if no unused name is found (`TempError::NameSpaceExhausted`), or if file creation fails.
+    pub fn build(&self) -> TempResult<TempFile> {
+        let _scope = self.config.as_ref().map(TempConfig::scoped);
+        let config = TempConfig::current();
+        let dir = resolve_dir(self.dir.as_deref(), self.location.as_ref())?;
+        let defaults = FileSpec::default();
+        let spec = FileSpec {
+            mode: self.mode.unwrap_or(defaults.mode),
+            append: self.append,
+            read_only: self.read_only,
+        };
+        create_unique(
+            &dir,
+            &self.prefix,
+            &self.suffix,
+            self.rand_len.unwrap_or(config.name_len),
+            config.retries,
+            self.names.as_deref().unwrap_or(&*config.names),
+            |path| TempFile::create(path, &spec),
+        )
+    }
+}
+
+/// A builder for `TempDir`s with a custom name and permission mode.
+///
+/// Names are of the form `{prefix}{random}{suffix}`, for example `build-Xa3k`.
+#[derive(Debug, Clone, Default)]
+pub struct TempDirBuilder {
+    /// Text placed before the random part of the name.
+    prefix: String,
+    /// Text placed after the random part of the name.
+    suffix: String,
+    /// Length of the random part of the name, or `None` for the default.
+    rand_len: Option<usize>,
+    /// Permission mode of the created directory, or `None` for the default.
+    mode: Option<u32>,
+    /// The directory to create the directory in, or `None` for the base directory of the configuration.
+    dir: Option<PathBuf>,
+    /// Where to place the directory, or `None` for the base directory of the configuration.
+    location: Option<TempLocation>,
+    /// The generator of the middle part of the name, or `None` for the one of the configuration.
+    names: Option<Arc<dyn NameGenerator>>,
+    /// The configuration in effect while building, or `None` for the current one of the thread.
+    config: Option<TempConfig>,
+    /// Whether to write an owner marker into the created directory.
+    owner_marker: bool,
+    /// Whether the created directory is kept if it is dropped during a panic, or `None` for the cleanup policy of the configuration.
+    keep_on_panic: Option<bool>,
+    /// Whether the created directory is kept if it is dropped while `TEMPFS_KEEP=1` is set, or `None` for the cleanup policy of the configuration.
+    keep_if_env: Option<bool>,
+}
+
+impl TempDirBuilder {
+    /// Creates a new builder with an empty prefix and suffix and the default random part length.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the text placed before the random part of the name.
+    pub fn prefix<S: AsRef<str>>(&mut self, prefix: S) -> &mut Self {
+        self.prefix = prefix.as_ref().to_string();
+        self
+    }
+
+    /// Sets the text placed after the random part of the name.
+    pub fn suffix<S: AsRef<str>>(&mut self, suffix: S) -> &mut Self {
+        self.suffix = suffix.as_ref().to_string();
+        self
+    }
+
+    /// Sets the length of the random part of the name. A length of zero uses exactly `{prefix}{suffix}`.
+    pub fn rand_len(&mut self, len: usize) -> &mut Self {
+        self.rand_len = Some(len);
+        self
+    }
+
+    /// Sets the permission mode of the created directory and any missing parents, subject to the umask. Defaults to the directory mode of the configuration. Ignored on non-Unix platforms.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets the directory to create the directory in. If a relative directory is provided, it is resolved relative to the location (the base directory of the configuration by default).
+    pub fn dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the storage location to create the directory in, such as a RAM-backed or large disk. It is resolved each time the builder builds.
+    pub fn location(&mut self, location: TempLocation) -> &mut Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Sets whether an owner marker is written into the created directory, letting a `Reaper` remove it if this process dies without cleaning up.
+    pub fn owner_marker(&mut self, owner_marker: bool) -> &mut Self {
+        self.owner_marker = owner_marker;
+        self
+    }
+
+    /// Sets the generator of the part of the name between the prefix and suffix, such as `SeededNames` for reproducible names.
+    ///
+    /// Clones of the builder share the generator, so they continue the same sequence.
+    pub fn name_generator<G: NameGenerator + 'static>(&mut self, names: G) -> &mut Self {
+        self.names = Some(Arc::new(names));
+        self
+    }
+
+    /// Sets whether the created directory is kept if it is dropped while the thread is panicking, see [`TempDir::keep_on_panic`].
+    ///
+    /// Either value overrides the cleanup policy of the configuration.
+    pub fn keep_on_panic(&mut self, keep: bool) -> &mut Self {
+        self.keep_on_panic = Some(keep);
+        self
+    }
+
+    /// Sets whether the created directory is kept if it is dropped while `TEMPFS_KEEP=1` is set, see [`TempDir::keep_if_env`].
+    ///
+    /// Either value overrides the cleanup policy of the configuration.
+    pub fn keep_if_env(&mut self, keep: bool) -> &mut Self {
+        self.keep_if_env = Some(keep);
+        self
+    }
+
+    /// Sets the configuration used while building, in place of the current one of the thread, see [`TempConfig`].
+    ///
+    /// Settings made directly on the builder take precedence over it.
+    pub fn config(&mut self, config: TempConfig) -> &mut Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Creates the temporary directory.
+    ///
+    /// Unlike `TempDir::new`, an existing directory is never adopted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the location cannot be resolved, if no unused name is found (`TempError::NameSpaceExhausted`), if directory creation fails, or if the owner marker cannot be written.
+    pub fn build(&self) -> TempResult<TempDir> {
+        let _scope = self.config.as_ref().map(TempConfig::scoped);
+        let config = TempConfig::current();
+        let dir = resolve_dir(self.dir.as_deref(), self.location.as_ref())?;
+        let mode = self.mode.unwrap_or(config.dir_mode);
+        let mut temp_dir = create_unique(
+            &dir,
+            &self.prefix,
+            &self.suffix,
+            self.rand_len.unwrap_or(config.name_len),
+            config.retries,
+            self.names.as_deref().unwrap_or(&*config.names),
+            |path| TempDir::create(path, mode, true),
+        )?;
+        if self.owner_marker {
+            temp_dir.mark_owner()?;
+        }
+        let policy = temp_dir.cleanup_policy_mut();
+        if let Some(keep) = self.keep_on_panic {
+            policy.keep_on_panic = keep;
+        }
+        if let Some(keep) = self.keep_if_env {
+            policy.keep_if_env = keep;
+        }
+        Ok(temp_dir)
+    }
+}
@@ -0,0 +1,440 @@
+#[cfg(unix)]
+use std::ffi::{CString, OsStr};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::helpers::path_to_cstring;
+
+/// A process-wide callback invoked when a `Drop` implementation fails to remove a temporary entry.
+type CleanupHook = Box<dyn Fn(&Path, &io::Error) + Send + Sync>;
+
+/// The installed cleanup hook, if any.
+static CLEANUP_HOOK: RwLock<Option<CleanupHook>> = RwLock::new(None);
+
+/// Installs a process-wide hook which is called whenever dropping a `TempFile`, `TempDir` or other temporary object fails to remove an entry.
+///
+/// `Drop` cannot return errors, so without a hook such failures are silently ignored. Replaces any previously installed hook.
+pub fn set_cleanup_hook<F>(hook: F)
+where
+    F: Fn(&Path, &io::Error) + Send + Sync + 'static,
+{
+    *CLEANUP_HOOK
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Box::new(hook));
+}
+
+/// Removes the process-wide cleanup hook, if one is installed.
+pub fn clear_cleanup_hook() {
+    *CLEANUP_HOOK
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+}
+
+/// Passes a failure to remove `path` during `Drop` to the installed hook, if any.
+pub(crate) fn report_drop_failure(path: &Path, error: &io::Error) {
+    if let Some(ref hook) = *CLEANUP_HOOK
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+    {
+        hook(path, error);
+    }
+}
+
+/// An entry which could not be removed during cleanup.
+#[derive(Debug)]
+pub struct CleanupFailure {
+    /// The path of the entry.
+    pub path: PathBuf,
+    /// Why the entry could not be removed, e.g. `EACCES`, `EBUSY` or `ENOTEMPTY`.
+    pub error: io::Error,
+}
+
+/// The outcome of cleaning up a temporary directory, listing every entry which could not be removed.
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    /// The entries which could not be removed.
+    failures: Vec<CleanupFailure>,
+}
+
+impl CleanupReport {
+    /// Checks if every entry was removed.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Returns the entries which could not be removed.
+    #[must_use]
+    pub fn failures(&self) -> &[CleanupFailure] {
+        &self.failures
+    }
+
+    /// Records that `path` could not be removed.
+    pub(crate) fn push(&mut self, path: &Path, error: io::Error) {
+        self.failures.push(CleanupFailure {
+            path: path.to_path_buf(),
+            error,
+        });
+    }
+}
+
+impl Display for CleanupReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.failures.is_empty() {
+            return write!(f, "All entries removed");
+        }
+        write!(f, "{} entries could not be removed:", self.failures.len())?;
+        for failure in &self.failures {
+            write!(f, "\n  {}: {}", failure.path.display(), failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of times a removal which fails because the entry is busy is attempted before giving up.
+const BUSY_ATTEMPTS: u32 = 5;
+
+/// Runs a removal, retrying it with a short, growing delay while it fails because the entry is busy (`EBUSY`).
+fn retry_busy<F: FnMut() -> io::Result<()>>(mut remove: F) -> io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match remove() {
+            Err(e) if e.kind() == io::ErrorKind::ResourceBusy && attempt < BUSY_ATTEMPTS => {
+                thread::sleep(Duration::from_millis(10 << attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Removes the temporary file `path`, then the parent directories created for it up to `created_parent`, as far as they are empty.
+///
+/// On failure, returns the path which could not be removed along with the error. Entries which no longer exist are ignored.
+pub(crate) fn remove_file_and_parents(
+    path: &Path,
+    created_parent: Option<&Path>,
+) -> Result<(), (PathBuf, io::Error)> {
+    match retry_busy(|| fs::remove_file(path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err((path.to_path_buf(), e)),
+        _ => {}
+    }
+    match created_parent {
+        Some(created) => remove_empty_parents(path, created),
+        None => Ok(()),
+    }
+}
+
+/// Removes the temporary directory `root` and everything in it with [`remove_dir_reporting`], then the parent directories created
+/// for it up to `created_parent`, as far as they are empty.
+///
+/// # Errors
+///
+/// Returns an error only if `root` itself cannot be opened or listed.
+pub(crate) fn remove_dir_and_parents(
+    root: &Path,
+    created_parent: Option<&Path>,
+    report: &mut CleanupReport,
+) -> io::Result<()> {
+    remove_dir_reporting(root, report)?;
+    if let Some(created) = created_parent {
+        if let Err((path, e)) = remove_empty_parents(root, created) {
+            report.push(&path, e);
+        }
+    }
+    Ok(())
+}
+
+/// Removes the directories from the parent of `entry` up to and including `created`, stopping at the first which is not empty.
+///
+/// Directories created for an entry may have been filled by other code since, so they are never removed recursively. If `entry`
+/// has been moved out from under `created`, only `created` itself is attempted.
+pub(crate) fn remove_empty_parents(
+    entry: &Path,
+    created: &Path,
+) -> Result<(), (PathBuf, io::Error)> {
+    let mut dir = match entry.parent() {
+        Some(parent) if parent.starts_with(created) => parent,
+        _ => created,
+    };
+    loop {
+        match retry_busy(|| fs::remove_dir(dir)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => return Ok(()),
+            #[cfg(unix)]
+            Err(e) if e.raw_os_error() == Some(libc::EEXIST) => return Ok(()),
+            Err(e) => return Err((dir.to_path_buf(), e)),
+        }
+        if dir == created {
+            return Ok(());
+        }
+        match dir.parent() {
+            Some(parent) => dir = parent,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Removes the directory `root` and everything in it, continuing past entries which cannot be removed and recording them in `report`.
+///
+/// The tree is walked through directory file descriptors with `openat(O_NOFOLLOW)` and `unlinkat`, so a symlink inside it, or one
+/// swapped in for a directory during the walk, is removed rather than followed. Directories lacking write or search permission are
+/// given `u+rwx` so their entries can be removed, and removals failing with `EBUSY` are retried a few times.
+///
+/// Entries which no longer exist are not considered failures.
+///
+/// # Errors
+///
+/// Returns an error only if `root` itself cannot be opened or listed.
+#[cfg(unix)]
+pub(crate) fn remove_dir_reporting(root: &Path, report: &mut CleanupReport) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let (Some(parent), Some(name)) = (root.parent(), root.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Cannot remove a filesystem root",
+        ));
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    let parent_fd = match fd::open_dir(libc::AT_FDCWD, &path_to_cstring(parent)?, false) {
+        Ok(fd) => fd,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let name = CString::new(name.as_bytes())?;
+    let is_dir = match fd::lstat_at(&parent_fd, &name) {
+        Ok(stat) => stat.st_mode & libc::S_IFMT == libc::S_IFDIR,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if is_dir {
+        let dir = fd::open_dir_fixing(&parent_fd, &name)?;
+        remove_contents(&dir, root, report)?;
+    }
+    // A symlink swapped in for the root is removed itself; the parent is not part of the tree, so its permissions are left alone.
+    let flags = if is_dir { libc::AT_REMOVEDIR } else { 0 };
+    match retry_busy(|| fd::unlink_at(&parent_fd, &name, flags)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => report.push(root, e),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Removes everything in the open directory `dir`, whose path is `path`, recording failures in `report`.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be listed.
+#[cfg(unix)]
+fn remove_contents(dir: &OwnedFd, path: &Path, report: &mut CleanupReport) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    for name in fd::list_dir(dir)? {
+        let entry_path = path.join(OsStr::from_bytes(name.as_bytes()));
+        let is_dir = match fd::lstat_at(dir, &name) {
+            Ok(stat) => stat.st_mode & libc::S_IFMT == libc::S_IFDIR,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                report.push(&entry_path, e);
+                continue;
+            }
+        };
+        if is_dir {
+            match fd::open_dir_fixing(dir, &name) {
+                Ok(child) => {
+                    if let Err(e) = remove_contents(&child, &entry_path, report) {
+                        report.push(&entry_path, e);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    report.push(&entry_path, e);
+                    continue;
+                }
+            }
+        }
+        let flags = if is_dir { libc::AT_REMOVEDIR } else { 0 };
+        let mut result = retry_busy(|| fd::unlink_at(dir, &name, flags));
+        if matches!(result, Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied) {
+            result = fd::make_writable(dir)
+                .and_then(|()| retry_busy(|| fd::unlink_at(dir, &name, flags)));
+        }
+        match result {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => report.push(&entry_path, e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Thin wrappers around the `*at` family of system calls used to walk a directory tree without following symlinks.
+#[cfg(unix)]
+mod fd {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+    /// Opens the directory `name` relative to `dir`, refusing to follow a symlink in its place if `nofollow` is set.
+    pub(super) fn open_dir(dir: RawFd, name: &CStr, nofollow: bool) -> io::Result<OwnedFd> {
+        let mut flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+        if nofollow {
+            flags |= libc::O_NOFOLLOW;
+        }
+        // SAFETY: `name` is a valid NUL-terminated string which outlives the call.
+        let fd = unsafe { libc::openat(dir, name.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just opened and is owned by nothing else.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Opens the subdirectory `name` of `dir` without following symlinks, granting it `u+rwx` first if it cannot be searched or listed.
+    pub(super) fn open_dir_fixing(dir: &OwnedFd, name: &CStr) -> io::Result<OwnedFd> {
+        match open_dir(dir.as_raw_fd(), name, true) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                let mode = lstat_at(dir, name)?.st_mode & 0o7777;
+                // SAFETY: `name` is a valid NUL-terminated string which outlives the call.
+                let ret =
+                    unsafe { libc::fchmodat(dir.as_raw_fd(), name.as_ptr(), mode | 0o700, 0) };
+                if ret != 0 {
+                    return Err(e);
+                }
+                open_dir(dir.as_raw_fd(), name, true)
+            }
+            result => result,
+        }
+    }
+
+    /// Returns the status of `name` relative to `dir`, without following a symlink.
+    pub(super) fn lstat_at(dir: &OwnedFd, name: &CStr) -> io::Result<libc::stat> {
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        // SAFETY: `name` is a valid NUL-terminated string and `stat` is valid for writes of a `libc::stat`.
+        let ret = unsafe {
+            libc::fstatat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                stat.as_mut_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fstatat` succeeded, so it initialized `stat`.
+        Ok(unsafe { stat.assume_init() })
+    }
+
+    /// Removes `name` relative to `dir`; `flags` is `AT_REMOVEDIR` for directories and `0` otherwise.
+    pub(super) fn unlink_at(dir: &OwnedFd, name: &CStr, flags: libc::c_int) -> io::Result<()> {
+        // SAFETY: `name` is a valid NUL-terminated string which outlives the call.
+        if unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), flags) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Grants the open directory `dir` `u+rwx`, so that its entries can be removed.
+    pub(super) fn make_writable(dir: &OwnedFd) -> io::Result<()> {
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        // SAFETY: `stat` is valid for writes of a `libc::stat`.
+        if unsafe { libc::fstat(dir.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fstat` succeeded, so it initialized `stat`.
+        let mode = unsafe { stat.assume_init() }.st_mode & 0o7777;
+        // SAFETY: `fchmod` has no memory safety requirements.
+        if unsafe { libc::fchmod(dir.as_raw_fd(), mode | 0o700) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Lists the names of the entries in the open directory `dir`, excluding `.` and `..`.
+    pub(super) fn list_dir(dir: &OwnedFd) -> io::Result<Vec<CString>> {
+        // `fdopendir` takes ownership of the descriptor it is given, so it gets a duplicate.
+        let dup = dir.try_clone()?;
+        // SAFETY: `dup` is a valid, open directory descriptor whose ownership is transferred to the stream.
+        let stream = unsafe { libc::fdopendir(std::os::fd::IntoRawFd::into_raw_fd(dup)) };
+        if stream.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let mut names = Vec::new();
+        loop {
+            // SAFETY: `stream` is a valid directory stream which is only used by this thread.
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                break;
+            }
+            // SAFETY: `readdir` returned a valid entry whose name is NUL-terminated.
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            if name != c"." && name != c".." {
+                names.push(name.to_owned());
+            }
+        }
+        // SAFETY: `stream` is valid and is not used after being closed.
+        unsafe { libc::closedir(stream) };
+        Ok(names)
+    }
+}
+
+/// Removes the directory `root` and everything in it, continuing past entries which cannot be removed and recording them in `report`.
+///
+/// Entries which no longer exist are not considered failures.
+///
+/// # Errors
+///
+/// Returns an error only if `root` itself cannot be listed.
+#[cfg(not(unix))]
+pub(crate) fn remove_dir_reporting(root: &Path, report: &mut CleanupReport) -> io::Result<()> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        match entry {
+            Ok(entry) => remove_entry_reporting(&entry.path(), report),
+            Err(e) => report.push(root, e),
+        }
+    }
+    match retry_busy(|| fs::remove_dir(root)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => report.push(root, e),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Removes a single entry (recursively for directories, without following symlinks), recording failures in `report`.
+#[cfg(not(unix))]
+fn remove_entry_reporting(path: &Path, report: &mut CleanupReport) {
+    let result = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => {
+            if let Err(e) = remove_dir_reporting(path, report) {
+                report.push(path, e);
+            }
+            return;
+        }
+        Ok(_) => retry_busy(|| fs::remove_file(path)),
+        Err(e) => Err(e),
+    };
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => report.push(path, e),
+        _ => {}
+    }
+}
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::TempResult;
+use crate::global_consts::{
+    DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, DEFAULT_NAME_LEN, DEFAULT_NUM_RETRY,
+};
+use crate::location::TempLocation;
+use crate::name_gen::{DefaultNames, NameGenerator};
+
+thread_local! {
+    /// The configuration overriding the defaults on this thread, if any.
+    static CURRENT: RefCell<Option<TempConfig>> = const { RefCell::new(None) };
+}
+
+/// When a `TempDir` is kept on drop instead of being removed, see [`TempDir::keep_on_panic`](crate::TempDir::keep_on_panic)
+/// and [`TempDir::keep_if_env`](crate::TempDir::keep_if_env).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CleanupPolicy {
+    /// Keep the directory if it is dropped while the thread is panicking.
+    pub keep_on_panic: bool,
+    /// Keep the directory if it is dropped while `TEMPFS_KEEP=1` is set.
+    pub keep_if_env: bool,
+}
+
+/// Settings for creating temporary files and directories: naming, retries, base directory, default modes and cleanup policy.
+///
+/// A configuration is attached to a builder with `config`, or made current for the calling thread with [`scoped`](TempConfig::scoped)
+/// or [`set_thread_local`](TempConfig::set_thread_local). Every constructor without explicit settings of its own, such as
+/// `TempFile::new` or `TempDir::new_random`, then uses it. Other threads are unaffected, so different tests in the same binary can
+/// use different settings.
+#[derive(Debug, Clone)]
+pub struct TempConfig {
+    /// The generator of random names.
+    pub(crate) names: Arc<dyn NameGenerator>,
+    /// The length of random names.
+    pub(crate) name_len: usize,
+    /// The total number of names tried before giving up, at least one.
+    pub(crate) retries: usize,
+    /// Where relative paths are resolved.
+    pub(crate) location: TempLocation,
+    /// The permission mode new files are created with.
+    pub(crate) file_mode: u32,
+    /// The permission mode new directories, including missing parents, are created with.
+    pub(crate) dir_mode: u32,
+    /// When new directories are kept instead of removed.
+    pub(crate) cleanup: CleanupPolicy,
+}
+
+impl Default for TempConfig {
+    fn default() -> Self {
+        Self {
+            names: Arc::new(DefaultNames),
+            name_len: DEFAULT_NAME_LEN,
+            retries: DEFAULT_NUM_RETRY,
+            location: TempLocation::SystemTemp,
+            file_mode: DEFAULT_FILE_MODE,
+            dir_mode: DEFAULT_DIR_MODE,
+            cleanup: CleanupPolicy::default(),
+        }
+    }
+}
+
+impl TempConfig {
+    /// Creates a configuration with the default settings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the generator of random names, such as `SeededNames` for reproducible names.
+    pub fn name_generator<G: NameGenerator + 'static>(&mut self, names: G) -> &mut Self {
+        self.names = Arc::new(names);
+        self
+    }
+
+    /// Sets the length of random names. Defaults to 16.
+    pub fn name_len(&mut self, len: usize) -> &mut Self {
+        self.name_len = len;
+        self
+    }
+
+    /// Sets the total number of names tried before giving up on finding an unused one with `TempError::NameSpaceExhausted`.
+    /// This counts the first attempt, and a value of zero is treated as one. Defaults to 1024.
+    pub fn retries(&mut self, retries: usize) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the storage location relative paths are resolved in. Defaults to the system temporary directory.
+    pub fn location(&mut self, location: TempLocation) -> &mut Self {
+        self.location = location;
+        self
+    }
+
+    /// Sets the directory relative paths are resolved in.
+    pub fn base_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.location = TempLocation::Dir(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the permission mode new files are created with, subject to the umask. Defaults to 0o600. Ignored on non-Unix platforms.
+    pub fn file_mode(&mut self, mode: u32) -> &mut Self {
+        self.file_mode = mode;
+        self
+    }
+
+    /// Sets the permission mode new directories and missing parent directories are created with, subject to the umask. Defaults to 0o700.
+    /// Ignored on non-Unix platforms.
+    pub fn dir_mode(&mut self, mode: u32) -> &mut Self {
+        self.dir_mode = mode;
+        self
+    }
+
+    /// Sets when new directories are kept instead of removed on drop.
+    pub fn cleanup_policy(&mut self, policy: CleanupPolicy) -> &mut Self {
+        self.cleanup = policy;
+        self
+    }
+
+    /// Returns the configuration in effect on the calling thread: the thread-local one if set, and otherwise the defaults.
+    #[must_use]
+    pub fn current() -> Self {
+        CURRENT
+            .with(|current| current.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    /// Sets the configuration in effect on the calling thread until it is changed again, or restores the defaults if `None`.
+    ///
+    /// Returns the previous thread-local configuration, if any.
+    pub fn set_thread_local(config: Option<Self>) -> Option<Self> {
+        CURRENT.with(|current| current.replace(config))
+    }
+
+    /// Makes a copy of this configuration current on the calling thread until the returned guard is dropped, which restores
+    /// the previous one.
+    #[must_use = "the configuration is only in effect while the guard is alive"]
+    pub fn scoped(&self) -> ConfigGuard {
+        ConfigGuard {
+            previous: Self::set_thread_local(Some(self.clone())),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Restores the previous configuration of the thread when dropped, see [`TempConfig::scoped`].
+#[derive(Debug)]
+pub struct ConfigGuard {
+    /// The thread-local configuration which was in effect before the guard was created.
+    previous: Option<TempConfig>,
+    /// Keeps the guard on the thread whose configuration it restores.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ConfigGuard {
+    fn drop(&mut self) {
+        TempConfig::set_thread_local(self.previous.take());
+    }
+}
+
+/// Resolves the directory relative paths are placed in under the current configuration.
+pub(crate) fn base_dir() -> TempResult<PathBuf> {
+    TempConfig::current().location.resolve()
+}
@@ -3,45 +3,288 @@ use regex::Error as RErr;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 /// Errors that can occur when using `TempDir` or `TempFile`.
 pub enum TempError {
     /// Occurs when attempting access a `None` file--one which was already closed.
     FileIsNone,
-    /// A given file or path do not match.
-    InvalidFileOrPath,
-    /// An IO error.
+    /// A given file or path do not match, such as a path outside a `TempDir` or one without a file name.
+    InvalidFileOrPath(PathBuf),
+    /// An IO error without further context, such as one from reading or writing an open file.
     IO(io::Error),
+    /// An IO error while performing an operation on a path.
+    Failed {
+        /// The operation which failed.
+        operation: Operation,
+        /// The path the operation was performed on.
+        path: PathBuf,
+        /// The underlying error.
+        source: io::Error,
+    },
     #[cfg(feature = "regex_support")]
     /// A `RegEx` error.
     Regex(RErr),
     /// The given path already exists.
     PathExists(PathBuf),
+    /// A path given to a `TempDir` is absolute, climbs out of the directory with `..`, or passes through a symlink.
+    EscapesDirectory(PathBuf),
+    /// A step of a durable persist failed.
+    Persist {
+        /// The step which failed.
+        step: PersistStep,
+        /// The path the step was performed on: the file for `SyncFile`, the target for `Rename` and the directory for `SyncDir`.
+        path: PathBuf,
+        /// The underlying error.
+        source: io::Error,
+    },
+    /// No unused name was found for a randomly named temporary file or directory.
+    NameSpaceExhausted {
+        /// The number of names tried.
+        attempts: usize,
+        /// The directory the names were tried in.
+        dir: PathBuf,
+    },
+    /// A write or resize would have grown a file, or the files of a directory, past their byte limit.
+    QuotaExceeded {
+        /// The limit, in bytes.
+        limit: u64,
+        /// The size, in bytes, the file or directory would have reached.
+        attempted: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The steps of a durable persist, used to report which one failed.
+pub enum PersistStep {
+    /// Syncing the file's contents to the storage device.
+    SyncFile,
+    /// Atomically renaming the file to its target.
+    Rename,
+    /// Syncing the parent directories so the rename itself is durable.
+    SyncDir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The filesystem operations reported by `TempError::Failed`.
+pub enum Operation {
+    /// Creating the missing parent directories of a file or directory.
+    CreateParent,
+    /// Creating a file.
+    CreateFile,
+    /// Creating a directory.
+    CreateDir,
+    /// Opening an existing file.
+    Open,
+    /// Changing the permissions of a file.
+    SetPermissions,
+    /// Copying the contents of a file.
+    Copy,
+    /// Renaming a file.
+    Rename,
+    /// Removing a file or directory.
+    Remove,
+    /// Syncing a file to the storage device.
+    Sync,
+    /// Listing the entries of a directory.
+    ReadDir,
+    /// Taking a lock on a file.
+    Lock,
+    /// Truncating or extending a file.
+    Resize,
+    /// Reading the metadata of a file.
+    Metadata,
+    /// Flushing buffered writes to a file.
+    Flush,
+    /// Adding seals to a memfd-backed file.
+    Seal,
+    /// Changing the flags of a file descriptor.
+    SetFdFlags,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreateParent => write!(f, "creating parent directories"),
+            Self::CreateFile => write!(f, "creating file"),
+            Self::CreateDir => write!(f, "creating directory"),
+            Self::Open => write!(f, "opening file"),
+            Self::SetPermissions => write!(f, "setting permissions"),
+            Self::Copy => write!(f, "copying file"),
+            Self::Rename => write!(f, "renaming file"),
+            Self::Remove => write!(f, "removing"),
+            Self::Sync => write!(f, "syncing file"),
+            Self::ReadDir => write!(f, "listing directory"),
+            Self::Lock => write!(f, "locking file"),
+            Self::Resize => write!(f, "resizing file"),
+            Self::Metadata => write!(f, "reading metadata of"),
+            Self::Flush => write!(f, "flushing file"),
+            Self::Seal => write!(f, "sealing file"),
+            Self::SetFdFlags => write!(f, "setting descriptor flags of"),
+        }
+    }
+}
+
+impl Display for PersistStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SyncFile => write!(f, "syncing file"),
+            Self::Rename => write!(f, "renaming file"),
+            Self::SyncDir => write!(f, "syncing parent directory"),
+        }
+    }
 }
 
 impl Display for TempError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::FileIsNone => write!(f, "File is None"),
-            Self::InvalidFileOrPath => write!(f, "File or path is invalid"),
+            Self::InvalidFileOrPath(path) => {
+                write!(f, "File or path is invalid: {}", path.display())
+            }
             Self::IO(e) => write!(f, "IO error: {e}"),
+            Self::Failed {
+                operation,
+                path,
+                source,
+            } => write!(f, "IO error while {operation} {}: {source}", path.display()),
             #[cfg(feature = "regex_support")]
             Self::Regex(e) => write!(f, "Regex error: {e}"),
             Self::PathExists(path) => write!(f, "Entry at path already exists: {}", path.display()),
+            Self::EscapesDirectory(path) => write!(
+                f,
+                "Path escapes the temporary directory: {}",
+                path.display()
+            ),
+            Self::Persist { step, path, source } => write!(
+                f,
+                "Persist failed while {step} {}: {source}",
+                path.display()
+            ),
+            Self::NameSpaceExhausted { attempts, dir } => write!(
+                f,
+                "No unused name found in {} after {attempts} attempts",
+                dir.display()
+            ),
+            Self::QuotaExceeded { limit, attempted } => write!(
+                f,
+                "Quota of {limit} bytes exceeded: attempted to reach {attempted} bytes"
+            ),
         }
     }
 }
 
-impl Error for TempError {}
+impl TempError {
+    /// Creates a `TempError::Failed` for an IO error while performing `operation` on `path`.
+    pub(crate) fn failed(operation: Operation, path: &Path, source: io::Error) -> Self {
+        Self::Failed {
+            operation,
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Returns the kind of IO error closest to this error, for callers which branch on `io::ErrorKind`.
+    ///
+    /// IO errors report their own kind; the other variants map to the kind an equivalent IO error would have.
+    #[must_use]
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Self::IO(e) | Self::Failed { source: e, .. } | Self::Persist { source: e, .. } => {
+                e.kind()
+            }
+            Self::FileIsNone => io::ErrorKind::NotFound,
+            Self::InvalidFileOrPath(_) | Self::EscapesDirectory(_) => io::ErrorKind::InvalidInput,
+            #[cfg(feature = "regex_support")]
+            Self::Regex(_) => io::ErrorKind::InvalidInput,
+            Self::PathExists(_) | Self::NameSpaceExhausted { .. } => io::ErrorKind::AlreadyExists,
+            Self::QuotaExceeded { .. } => io::ErrorKind::QuotaExceeded,
+        }
+    }
+
+    /// Returns the operation which failed, if the error carries one.
+    #[must_use]
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            Self::Failed { operation, .. } => Some(*operation),
+            _ => None,
+        }
+    }
+
+    /// Returns the path the error concerns, if it carries one.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Failed { path, .. }
+            | Self::Persist { path, .. }
+            | Self::InvalidFileOrPath(path)
+            | Self::PathExists(path)
+            | Self::EscapesDirectory(path)
+            | Self::NameSpaceExhausted { dir: path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl Error for TempError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IO(e) | Self::Failed { source: e, .. } | Self::Persist { source: e, .. } => {
+                Some(e)
+            }
+            #[cfg(feature = "regex_support")]
+            Self::Regex(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 /// Result type which uses a `TempError`
 pub type TempResult<T> = Result<T, TempError>;
 
 impl From<io::Error> for TempError {
+    /// Wraps an IO error, unwrapping a `TempError` which was carried through an IO trait such as `Write`.
     fn from(e: io::Error) -> Self {
-        Self::IO(e)
+        match e.downcast::<TempError>() {
+            Ok(inner) => inner,
+            Err(e) => Self::IO(e),
+        }
+    }
+}
+
+impl From<TempError> for io::Error {
+    /// Converts the error for use in functions returning `io::Result`, keeping it recoverable with `io::Error::downcast`.
+    ///
+    /// A `TempError::IO` is unwrapped; every other variant becomes the source of an IO error of the same [`kind`](TempError::kind).
+    fn from(e: TempError) -> Self {
+        match e {
+            TempError::IO(e) => e,
+            e => Self::new(e.kind(), e),
+        }
+    }
+}
+
+/// Attaches the operation and path to the IO error of a result.
+pub(crate) trait IoResultExt<T> {
+    /// Converts an IO error into `TempError::Failed` with the given operation and path.
+    fn context(self, operation: Operation, path: &Path) -> TempResult<T>;
+
+    /// Converts an IO error into `TempError::Persist` with the given step and path.
+    fn persist_context(self, step: PersistStep, path: &Path) -> TempResult<T>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn context(self, operation: Operation, path: &Path) -> TempResult<T> {
+        self.map_err(|e| TempError::failed(operation, path, e))
+    }
+
+    fn persist_context(self, step: PersistStep, path: &Path) -> TempResult<T> {
+        self.map_err(|source| TempError::Persist {
+            step,
+            path: path.to_path_buf(),
+            source,
+        })
     }
 }
 
@@ -1,5 +1,17 @@
 use std::env;
+#[cfg(unix)]
+use std::ffi::CString;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io;
 use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{IoResultExt, Operation, TempError, TempResult};
+use crate::global_consts::{DEFAULT_FILE_MODE, NAME_CHARS};
+use crate::reaper::STAGING_PREFIX;
+use crate::temp_file::PersistMode;
 
 /// A helper function to normalize a path without touching the filesystem.
 /// It removes redundant `.` components and resolves `..` without following symlinks.
@@ -22,6 +34,64 @@ pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
     normalized
 }
 
+/// Joins the relative path `name` onto `root`, checking that the result stays inside `root`.
+///
+/// `name` must not be absolute or climb above `root` with `..`, and no component of it which already exists on disk may be a symlink.
+/// The returned path is normalized.
+pub fn join_within(root: &Path, name: &Path) -> TempResult<PathBuf> {
+    let joined = join_within_lexically(root, name)?;
+    let mut current = root.to_path_buf();
+    for comp in joined.strip_prefix(root).unwrap_or(&joined).components() {
+        current.push(comp);
+        match fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                return Err(TempError::EscapesDirectory(root.join(name)))
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(joined)
+}
+
+/// Joins the relative path `name` onto `root` without touching the filesystem, rejecting absolute paths and `..` components which climb above `root`.
+pub fn join_within_lexically(root: &Path, name: &Path) -> TempResult<PathBuf> {
+    let mut relative = PathBuf::new();
+    for comp in name.components() {
+        match comp {
+            Component::Normal(c) => relative.push(c),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !relative.pop() {
+                    return Err(TempError::EscapesDirectory(root.join(name)));
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(TempError::EscapesDirectory(root.join(name)))
+            }
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        return Err(TempError::InvalidFileOrPath(root.join(name)));
+    }
+    Ok(root.join(relative))
+}
+
+/// Returns a `DirBuilder` which creates directories with the given permission mode (subject to the umask) on Unix.
+pub fn dir_builder(mode: u32) -> fs::DirBuilder {
+    #[allow(unused_mut)]
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    builder
+}
+
 /// Returns the path up to the first nonexistent directory component, or None if it all exists except the final component.
 pub fn first_missing_directory_component(path: &Path) -> Option<PathBuf> {
     // Get the parent directory (ignore the final component)
@@ -37,3 +107,332 @@ pub fn first_missing_directory_component(path: &Path) -> Option<PathBuf> {
     }
     None
 }
+
+/// Fills `buf` with random bytes from the best source available: the operating system with the `getrandom` feature, `rand` with
+/// the `rand_gen` feature, and otherwise randomly keyed std hashers.
+pub fn fill_random(buf: &mut [u8]) {
+    #[cfg(feature = "getrandom")]
+    if getrandom::fill(buf).is_ok() {
+        return;
+    }
+    #[cfg(feature = "rand_gen")]
+    {
+        use rand::RngCore;
+        rand::rng().fill_bytes(buf);
+    }
+    #[cfg(not(feature = "rand_gen"))]
+    {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+        for (round, chunk) in buf.chunks_mut(8).enumerate() {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(round);
+            hasher.write_u32(process::id());
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// Generates a random name of `len` characters.
+///
+/// With the `rand_gen` feature the characters are drawn from `NAME_CHARS` using `rand`; otherwise a randomly keyed std hasher is used.
+#[cfg(feature = "rand_gen")]
+pub fn random_name(len: usize) -> String {
+    use rand::Rng;
+    let mut rng = rand::rng();
+    (0..len)
+        .map(|_| NAME_CHARS[rng.random_range(0..NAME_CHARS.len())] as char)
+        .collect()
+}
+
+/// Generates a random name of `len` characters.
+///
+/// With the `rand_gen` feature the characters are drawn from `NAME_CHARS` using `rand`; otherwise a randomly keyed std hasher is used.
+#[cfg(not(feature = "rand_gen"))]
+pub fn random_name(len: usize) -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let state = RandomState::new();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let mut name = String::with_capacity(len);
+    let mut round = 0u64;
+    while name.len() < len {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(round);
+        hasher.write_u32(nanos);
+        hasher.write_u32(process::id());
+        let mut bits = hasher.finish();
+        // Each character consumes 6 bits.
+        for _ in 0..10 {
+            if name.len() == len {
+                break;
+            }
+            name.push(NAME_CHARS[(bits % NAME_CHARS.len() as u64) as usize] as char);
+            bits /= NAME_CHARS.len() as u64;
+        }
+        round += 1;
+    }
+    name
+}
+
+/// How an atomic move treated the source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    /// The source inode was renamed in place, so open handles still refer to it.
+    Renamed,
+    /// The source lived on another filesystem and was copied, synced and renamed into place; open handles refer to the removed original.
+    Copied,
+}
+
+/// Counter used to keep staging file names unique within the process.
+static STAGE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Moves `from` to `to` so that readers of `to` only ever observe the complete file.
+///
+/// Uses `rename(2)` when both paths share a filesystem. Across devices the file is copied to a hidden sibling of `to`, synced, and then renamed over `to`.
+pub fn move_file(from: &Path, to: &Path, mode: PersistMode) -> TempResult<MoveKind> {
+    match rename_with_mode(from, to, mode) {
+        Ok(()) => Ok(MoveKind::Renamed),
+        Err(e) if is_cross_device(&e) => {
+            let staged = copy_to_sibling(from, to)?;
+            if let Err(e) = rename_with_mode(&staged, to, mode) {
+                let _ = fs::remove_file(&staged);
+                return Err(map_move_error(e, to, mode));
+            }
+            fs::remove_file(from).context(Operation::Remove, from)?;
+            Ok(MoveKind::Copied)
+        }
+        Err(e) => Err(map_move_error(e, to, mode)),
+    }
+}
+
+/// Converts the error of a failed rename, reporting a refused overwrite as `TempError::PathExists`.
+fn map_move_error(e: io::Error, to: &Path, mode: PersistMode) -> TempError {
+    if mode == PersistMode::NoClobber && e.kind() == io::ErrorKind::AlreadyExists {
+        TempError::PathExists(to.to_path_buf())
+    } else {
+        TempError::failed(Operation::Rename, to, e)
+    }
+}
+
+/// Renames `from` to `to`, refusing to replace an existing entry when `mode` is `NoClobber`.
+fn rename_with_mode(from: &Path, to: &Path, mode: PersistMode) -> io::Result<()> {
+    match mode {
+        PersistMode::Overwrite => fs::rename(from, to),
+        PersistMode::NoClobber => rename_noreplace(from, to),
+    }
+}
+
+/// Atomically renames `from` to `to` with `renameat2(RENAME_NOREPLACE)`, falling back to `link`+`unlink` on filesystems which do not support the flag.
+#[cfg(target_os = "linux")]
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    let from_c = path_to_cstring(from)?;
+    let to_c = path_to_cstring(to)?;
+    // SAFETY: Both paths are valid NUL-terminated strings which outlive the call.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            from_c.as_ptr(),
+            libc::AT_FDCWD,
+            to_c.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EINVAL | libc::ENOSYS) => link_noreplace(from, to),
+        _ => Err(err),
+    }
+}
+
+/// Atomically renames `from` to `to` without replacing an existing entry, using `link`+`unlink`.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    link_noreplace(from, to)
+}
+
+/// Renames `from` to `to` without replacing an existing entry.
+///
+/// This is not atomic: the target may be created between the check and the rename.
+#[cfg(not(unix))]
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "Target path already exists",
+        ));
+    }
+    fs::rename(from, to)
+}
+
+/// Gives `from` the name `to` with a hard link (which fails if `to` exists), then removes `from`.
+#[cfg(unix)]
+fn link_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    fs::hard_link(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Syncs a directory, making changes to its entries (such as a rename into it) durable.
+#[cfg(unix)]
+pub fn sync_dir(path: &Path) -> io::Result<()> {
+    File::open(path)?.sync_all()
+}
+
+/// Syncs a directory, making changes to its entries (such as a rename into it) durable.
+///
+/// Directories cannot be opened for syncing on this platform, so this does nothing.
+#[cfg(not(unix))]
+pub fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Converts a path into a NUL-terminated string for passing to libc.
+#[cfg(unix)]
+pub fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+    CString::new(path.as_os_str().as_bytes()).map_err(Into::into)
+}
+
+/// Whether an error was caused by attempting to rename across filesystems.
+fn is_cross_device(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        e.raw_os_error() == Some(libc::EXDEV)
+    }
+    #[cfg(windows)]
+    {
+        // ERROR_NOT_SAME_DEVICE
+        e.raw_os_error() == Some(17)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Copies `from` into a new hidden file next to `to`, preserving permissions, and syncs it to disk.
+///
+/// Returns the path of the staged copy.
+fn copy_to_sibling(from: &Path, to: &Path) -> TempResult<PathBuf> {
+    let dir = to.parent().unwrap_or_else(|| Path::new("."));
+    let name = to
+        .file_name()
+        .ok_or_else(|| TempError::InvalidFileOrPath(to.to_path_buf()))?;
+    let mut source = File::open(from).context(Operation::Open, from)?;
+    let (staged, mut dest) =
+        create_hidden(dir, name, DEFAULT_FILE_MODE).context(Operation::CreateFile, dir)?;
+    let result = io::copy(&mut source, &mut dest)
+        .context(Operation::Copy, from)
+        .and_then(|_| {
+            source
+                .metadata()
+                .and_then(|m| dest.set_permissions(m.permissions()))
+                .context(Operation::SetPermissions, &staged)
+        })
+        .and_then(|()| dest.sync_all().context(Operation::Sync, &staged));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+    Ok(staged)
+}
+
+/// Returns a new hidden path in `dir` whose name is derived from `base` and unique within the process.
+///
+/// The name has the form `.tempfs-stage-{base}.{pid}-{counter}.tmp`, which a `Reaper` recognises as staging debris.
+pub fn hidden_name(dir: &Path, base: &OsStr) -> PathBuf {
+    let mut name = OsString::from(STAGING_PREFIX);
+    name.push(base);
+    name.push(format!(
+        ".{}-{}.tmp",
+        process::id(),
+        STAGE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    dir.join(name)
+}
+
+/// Creates a new, uniquely named hidden file in `dir` whose name is derived from `base`, opened for reading and writing.
+///
+/// On Unix the file is created with the given permission mode, subject to the umask.
+pub fn create_hidden(dir: &Path, base: &OsStr, mode: u32) -> io::Result<(PathBuf, File)> {
+    loop {
+        let path = hidden_name(dir, base);
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Opens a file in `dir` which has no name, using `O_TMPFILE` where the filesystem supports it.
+///
+/// Otherwise a hidden file is created and immediately unlinked, leaving a short window in which its name is visible.
+#[cfg(unix)]
+pub fn open_unnamed(dir: &Path, mode: u32) -> io::Result<File> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_TMPFILE)
+            .mode(mode)
+            .open(dir)
+        {
+            Ok(file) => return Ok(file),
+            // Older kernels report EISDIR, filesystems without support EOPNOTSUPP.
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(libc::EOPNOTSUPP | libc::EISDIR | libc::EINVAL)
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let (path, file) = create_hidden(dir, OsStr::new("anon"), mode)?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+
+/// Gives the unnamed file behind `file` the name `to` with `linkat` on its `/proc/self/fd` entry.
+///
+/// This only succeeds for files opened with `O_TMPFILE`; files which were unlinked after creation report `ENOENT`.
+#[cfg(target_os = "linux")]
+pub fn link_fd(file: &File, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let proc_path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd()))?;
+    let to_c = path_to_cstring(to)?;
+    // SAFETY: Both paths are valid NUL-terminated strings which outlive the call.
+    let ret = unsafe {
+        libc::linkat(
+            libc::AT_FDCWD,
+            proc_path.as_ptr(),
+            libc::AT_FDCWD,
+            to_c.as_ptr(),
+            libc::AT_SYMLINK_FOLLOW,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
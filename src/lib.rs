@@ -1,17 +1,48 @@
 //! A library primarily providing an interface to create temporary directories and files.
 //!
 //! It also provides several features:
-//! - `rand_gen` : Support for randomly generated file and directory names.
+//! - `rand_gen` : Draws randomly generated file and directory names from `rand`.
 //! - `mmap_support` : Support for memory mapping temporary files with memmap2.
 //! - `regex_support` : Support for searching temporary directory's contained files using regex.
 //! - `virt_fs` : Provides a virtual, in-memory filesystem with files, directories, permissions, metadata, and generally mimics a Linux filesystem.
-//! `display_files` : Allows Displaying `TempFile` and `VirtFile`.
+//! - `async` : Provides `AsyncTempFile` and asynchronous `TempDir` operations for tokio.
+//! - `getrandom` : Provides `OsRandomNames`, and draws random names and UUIDs directly from the operating system.
+//! - `display_files` : Allows Displaying `TempFile` and `VirtFile`.
 //! - `full` : Enables all of the above.
 
+#[cfg(feature = "async")]
+/// Module providing asynchronous temporary files for tokio.
+pub mod async_temp_file;
+/// Builders for temporary files and directories with custom names and options.
+pub mod builder;
+/// Fallible cleanup reporting and the process-wide hook for failures during `Drop`.
+pub mod cleanup;
+/// Scoped configuration of naming, retries, locations, modes and cleanup.
+pub mod config;
 /// Errors which can occur when using the types provided by tempfs.
 pub mod error;
 /// Global constants for the program.
 mod global_consts;
+/// Helpers for `temp_file` and `temp_dir`.
+mod helpers;
+/// Selection of where temporary files and directories are stored.
+pub mod location;
+#[cfg(unix)]
+/// Advisory locks on temporary files and lock files inside temporary directories.
+pub mod lock;
+#[cfg(feature = "mmap_support")]
+/// Module providing safe memory maps of temporary files.
+pub mod mapped_temp_file;
+/// Pluggable generation of names for temporary files and directories.
+pub mod name_gen;
+/// Byte limits on temporary files and directories.
+mod quota;
+/// Removal of temporary entries left behind by crashed processes.
+pub mod reaper;
+/// Opt-in registry of live temporary objects, removed on signals or process exit.
+pub mod registry;
+/// Module providing temporary files which are kept in memory until they grow large.
+pub mod spooled_temp_file;
 /// Module providing temporary directories.
 pub mod temp_dir;
 /// Module providing temporary files.
@@ -19,11 +50,28 @@ pub mod temp_file;
 #[cfg(feature = "virt_fs")]
 /// Module providing a virtual unix-like filesystem.
 pub mod virt_fs;
-/// Helpers for `temp_file` and `temp_dir`.
-mod helpers;
 
+#[cfg(feature = "async")]
+pub use async_temp_file::AsyncTempFile;
+pub use builder::{TempDirBuilder, TempFileBuilder};
+pub use cleanup::{clear_cleanup_hook, set_cleanup_hook, CleanupReport};
+pub use config::{CleanupPolicy, ConfigGuard, TempConfig};
 pub use error::*;
-pub use temp_dir::TempDir;
-pub use temp_file::TempFile;
+pub use location::TempLocation;
+#[cfg(unix)]
+pub use lock::{LockFile, TempFileLock};
+#[cfg(feature = "mmap_support")]
+pub use mapped_temp_file::{MapMode, MappedTempFile};
+#[cfg(feature = "getrandom")]
+pub use name_gen::OsRandomNames;
+pub use name_gen::{
+    DefaultNames, NameGenerator, SeededNames, SequentialNames, TimestampNames, UuidNames,
+};
+pub use reaper::Reaper;
+pub use spooled_temp_file::SpooledTempFile;
+pub use temp_dir::{DirTree, TempDir, TempEntry};
+#[cfg(target_os = "linux")]
+pub use temp_file::SealFlags;
+pub use temp_file::{PersistMode, TempFile};
 #[cfg(feature = "virt_fs")]
 pub use virt_fs::*;
@@ -0,0 +1,174 @@
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::TempResult;
+
+/// Where temporary files and directories are placed when no absolute path is given.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TempLocation {
+    /// The system temporary directory, as returned by `std::env::temp_dir`.
+    #[default]
+    SystemTemp,
+    /// A specific directory.
+    Dir(PathBuf),
+    /// A RAM-backed directory: `/dev/shm`, or any other writable `tmpfs` among the usual temporary directories. Linux only.
+    RamBacked,
+    /// The per-user runtime directory named by `XDG_RUNTIME_DIR`.
+    Runtime,
+    /// The candidate directory with the most free space, provided it has at least `min_free` bytes available.
+    ///
+    /// If `candidates` is empty, the system temporary directory, `/var/tmp`, `/dev/shm` and the runtime directory are considered.
+    LargestFree {
+        /// The directories to choose between. Candidates which do not exist or cannot be queried are skipped.
+        candidates: Vec<PathBuf>,
+        /// The minimum number of bytes which must be available in the chosen directory.
+        min_free: u64,
+    },
+}
+
+impl TempLocation {
+    /// Resolves the location to an existing directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `NotFound` if no RAM-backed or runtime directory exists, or of kind `StorageFull` if no
+    /// candidate whose free space can be queried has enough of it.
+    pub fn resolve(&self) -> TempResult<PathBuf> {
+        match self {
+            Self::SystemTemp => Ok(env::temp_dir()),
+            Self::Dir(dir) => Ok(dir.clone()),
+            Self::RamBacked => ram_backed_dir().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "No RAM-backed directory found").into()
+            }),
+            Self::Runtime => runtime_dir().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "XDG_RUNTIME_DIR is not set to a directory",
+                )
+                .into()
+            }),
+            Self::LargestFree {
+                candidates,
+                min_free,
+            } => {
+                let candidates = if candidates.is_empty() {
+                    default_candidates()
+                } else {
+                    candidates.clone()
+                };
+                let mut best: Option<(u64, PathBuf)> = None;
+                for dir in candidates.into_iter().filter(|d| d.is_dir()) {
+                    let Ok(free) = available_space(&dir) else {
+                        continue;
+                    };
+                    if free >= *min_free && best.as_ref().is_none_or(|(most, _)| free > *most) {
+                        best = Some((free, dir));
+                    }
+                }
+                best.map(|(_, dir)| dir).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::StorageFull,
+                        format!("No candidate directory has {min_free} bytes available"),
+                    )
+                    .into()
+                })
+            }
+        }
+    }
+}
+
+/// Returns the number of bytes available to unprivileged users on the filesystem containing `path`.
+///
+/// # Errors
+///
+/// Returns an error if the filesystem cannot be queried, or of kind `Unsupported` on non-Unix platforms.
+pub fn available_space<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        let path = crate::helpers::path_to_cstring(path.as_ref())?;
+        // SAFETY: A zeroed `statvfs` is a valid buffer for the call to fill in.
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: `path` is a valid NUL-terminated string and `stat` is a valid buffer, both outliving the call.
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        #[allow(clippy::useless_conversion)]
+        Ok(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Free space queries are only supported on Unix",
+        ))
+    }
+}
+
+/// Returns the runtime directory named by `XDG_RUNTIME_DIR`, if it is set to an existing absolute directory.
+fn runtime_dir() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|d| d.is_absolute() && d.is_dir())
+}
+
+/// The directories considered by `TempLocation::LargestFree` when no candidates are given.
+fn default_candidates() -> Vec<PathBuf> {
+    let mut candidates = vec![env::temp_dir()];
+    if cfg!(unix) {
+        candidates.push(PathBuf::from("/var/tmp"));
+    }
+    if cfg!(target_os = "linux") {
+        candidates.push(PathBuf::from("/dev/shm"));
+    }
+    candidates.extend(runtime_dir());
+    candidates
+}
+
+/// Finds a writable directory on a `tmpfs`, preferring `/dev/shm`.
+#[cfg(target_os = "linux")]
+fn ram_backed_dir() -> Option<PathBuf> {
+    let candidates = [
+        Some(PathBuf::from("/dev/shm")),
+        Some(PathBuf::from("/run/shm")),
+        runtime_dir(),
+        Some(env::temp_dir()),
+        Some(PathBuf::from("/tmp")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.is_dir() && is_tmpfs(dir) && is_writable(dir))
+}
+
+/// RAM-backed directories are only detected on Linux.
+#[cfg(not(target_os = "linux"))]
+fn ram_backed_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Checks if `dir` is on a `tmpfs`, using `statfs`.
+#[cfg(target_os = "linux")]
+fn is_tmpfs(dir: &Path) -> bool {
+    let Ok(path) = crate::helpers::path_to_cstring(dir) else {
+        return false;
+    };
+    // SAFETY: A zeroed `statfs` is a valid buffer for the call to fill in.
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid NUL-terminated string and `stat` is a valid buffer, both outliving the call.
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    stat.f_type == libc::TMPFS_MAGIC
+}
+
+/// Checks if the current user may create entries in `dir`.
+#[cfg(target_os = "linux")]
+fn is_writable(dir: &Path) -> bool {
+    let Ok(path) = crate::helpers::path_to_cstring(dir) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string which outlives the call.
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
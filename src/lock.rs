@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use crate::error::TempResult;
+use crate::temp_file::TempFile;
+
+/// Applies the `flock(2)` operation `op` to `fd`, retrying when interrupted by a signal.
+fn flock(fd: RawFd, op: libc::c_int) -> io::Result<()> {
+    loop {
+        // SAFETY: `flock` has no memory safety requirements; an invalid descriptor is reported as `EBADF`.
+        if unsafe { libc::flock(fd, op) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Takes an advisory lock on `fd`, returning `false` instead of blocking if `blocking` is unset and the lock is held elsewhere.
+pub(crate) fn lock_fd(fd: RawFd, exclusive: bool, blocking: bool) -> io::Result<bool> {
+    let mut op = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    if !blocking {
+        op |= libc::LOCK_NB;
+    }
+    match flock(fd, op) {
+        Ok(()) => Ok(true),
+        Err(e) if !blocking && e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Releases any advisory lock held on `fd`.
+pub(crate) fn unlock_fd(fd: RawFd) -> io::Result<()> {
+    flock(fd, libc::LOCK_UN)
+}
+
+/// An advisory lock on a `TempFile`, released when the guard is dropped.
+///
+/// The guard dereferences to the locked file, so it can be read from and written to while the lock is held.
+#[derive(Debug)]
+pub struct TempFileLock<'a> {
+    /// The locked file.
+    file: &'a mut TempFile,
+}
+
+impl<'a> TempFileLock<'a> {
+    /// Wraps a file on which a lock has just been taken.
+    pub(crate) fn new(file: &'a mut TempFile) -> Self {
+        Self { file }
+    }
+
+    /// Releases the lock, reporting any error instead of ignoring it like `Drop`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock cannot be released.
+    pub fn unlock(self) -> TempResult<()> {
+        let result = unlock_fd(self.file.as_raw_fd());
+        std::mem::forget(self);
+        Ok(result?)
+    }
+}
+
+impl Deref for TempFileLock<'_> {
+    type Target = TempFile;
+    fn deref(&self) -> &Self::Target {
+        self.file
+    }
+}
+
+impl DerefMut for TempFileLock<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.file
+    }
+}
+
+impl Drop for TempFileLock<'_> {
+    fn drop(&mut self) {
+        let _ = unlock_fd(self.file.as_raw_fd());
+    }
+}
+
+/// An exclusive lock on a lock file inside a `TempDir`, held until the guard is dropped.
+///
+/// The guard owns its own handle to the lock file, so other handles to the same file (in this or other processes) contend for the lock.
+#[derive(Debug)]
+pub struct LockFile {
+    /// The path of the lock file.
+    path: PathBuf,
+    /// The handle the lock is held through.
+    file: File,
+}
+
+impl LockFile {
+    /// Wraps a handle on which a lock has just been taken.
+    pub(crate) fn new(path: PathBuf, file: File) -> Self {
+        Self { path, file }
+    }
+
+    /// Returns the path of the lock file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the handle the lock is held through.
+    #[must_use]
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Releases the lock, reporting any error instead of ignoring it like `Drop`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock cannot be released.
+    pub fn unlock(self) -> TempResult<()> {
+        Ok(unlock_fd(self.file.as_raw_fd())?)
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = unlock_fd(self.file.as_raw_fd());
+    }
+}
@@ -0,0 +1,230 @@
+use memmap2::{Mmap, MmapMut, MmapOptions};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::error::TempResult;
+use crate::temp_file::TempFile;
+
+/// How a `MappedTempFile` maps its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapMode {
+    /// The mapping can only be read.
+    ReadOnly,
+    /// Writes to the mapping are written back to the file.
+    ReadWrite,
+    /// Writes to the mapping are private to it and never reach the file.
+    CopyOnWrite,
+}
+
+/// The memory map behind a `MappedTempFile`.
+#[derive(Debug)]
+enum Mapping {
+    /// A read-only map.
+    ReadOnly(Mmap),
+    /// A shared, writable map.
+    ReadWrite(MmapMut),
+    /// A private, writable map.
+    CopyOnWrite(MmapMut),
+}
+
+impl Mapping {
+    /// Returns the mapped bytes.
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::ReadOnly(map) => map,
+            Self::ReadWrite(map) | Self::CopyOnWrite(map) => map,
+        }
+    }
+}
+
+/// A memory map of a `TempFile`, which mutably borrows the file for as long as it is mapped.
+///
+/// Because of the borrow, the file cannot be truncated, renamed, persisted or dropped through its `TempFile` while mapped,
+/// which is what makes mapping it safe. As with any memory map, the file must not be truncated by other processes either;
+/// temporary files are created private to their owner, so this only happens if their permissions are changed.
+///
+/// The map never hands out the `TempFile` itself, so the file cannot be resized through it either:
+///
+/// ```compile_fail
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut file = tempfs::TempFile::new("mapped_set_len.bin")?;
+/// file.set_len(8192)?;
+/// let map = file.map()?;
+/// map.file().set_len(0)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut file = tempfs::TempFile::new("mapped_set_len.bin")?;
+/// file.set_len(8192)?;
+/// let map = file.map()?;
+/// file.set_len(0)?;
+/// assert_eq!(map[0], 0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MappedTempFile<'a> {
+    /// The mapped file.
+    file: &'a mut TempFile,
+    /// The current map of the file.
+    map: Mapping,
+}
+
+impl<'a> MappedTempFile<'a> {
+    /// Maps the whole file in the given mode.
+    pub(crate) fn new(file: &'a mut TempFile, mode: MapMode) -> TempResult<Self> {
+        let map = Self::map_file(file, mode)?;
+        Ok(Self { file, map })
+    }
+
+    /// Creates a map of the whole file.
+    fn map_file(file: &TempFile, mode: MapMode) -> TempResult<Mapping> {
+        let handle = file.file()?;
+        let options = MmapOptions::new();
+        // SAFETY: The caller holds the only borrow of the `TempFile`, so the file cannot be truncated or closed through it
+        // while the map exists, and its private permissions keep other processes from doing so.
+        let map = unsafe {
+            match mode {
+                MapMode::ReadOnly => Mapping::ReadOnly(options.map(handle)?),
+                MapMode::ReadWrite => Mapping::ReadWrite(options.map_mut(handle)?),
+                MapMode::CopyOnWrite => Mapping::CopyOnWrite(options.map_copy(handle)?),
+            }
+        };
+        Ok(map)
+    }
+
+    /// Returns the mode the file is mapped in.
+    #[must_use]
+    pub fn mode(&self) -> MapMode {
+        match self.map {
+            Mapping::ReadOnly(_) => MapMode::ReadOnly,
+            Mapping::ReadWrite(_) => MapMode::ReadWrite,
+            Mapping::CopyOnWrite(_) => MapMode::CopyOnWrite,
+        }
+    }
+
+    /// Returns the mapped bytes.
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        self.map.as_slice()
+    }
+
+    /// Returns the mapped bytes mutably.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `PermissionDenied` if the file is mapped read-only.
+    pub fn as_mut_slice(&mut self) -> TempResult<&mut [u8]> {
+        match self.map {
+            Mapping::ReadOnly(_) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Temporary file is mapped read-only",
+            )
+            .into()),
+            Mapping::ReadWrite(ref mut map) | Mapping::CopyOnWrite(ref mut map) => Ok(map),
+        }
+    }
+
+    /// Flushes the whole map to the file. This does nothing unless the file is mapped read-write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if flushing fails.
+    pub fn flush(&self) -> TempResult<()> {
+        if let Mapping::ReadWrite(ref map) = self.map {
+            map.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes `len` bytes of the map starting at `offset` to the file. This does nothing unless the file is mapped read-write.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the range in the map.
+    /// * `len` - The length of the range.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is out of bounds or if flushing fails.
+    pub fn flush_range(&self, offset: usize, len: usize) -> TempResult<()> {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > self.as_slice().len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Flush range is out of bounds of the map",
+            )
+            .into());
+        }
+        if let Mapping::ReadWrite(ref map) = self.map {
+            map.flush_range(offset, len)?;
+        }
+        Ok(())
+    }
+
+    /// Extends the file to `new_len` bytes and remaps it in the same mode. Does nothing if the file is already at least that long.
+    ///
+    /// Pending writes are flushed before remapping; in copy-on-write mode the private contents are carried over to the new map.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_len` - The new length of the file in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::QuotaExceeded)` if growing would exceed the file's quota, or an error if the file cannot be
+    /// extended or remapped. The previous map stays in place on failure.
+    pub fn grow(&mut self, new_len: u64) -> TempResult<()> {
+        let old_len = self.as_slice().len();
+        if u64::try_from(old_len).is_ok_and(|len| len >= new_len) {
+            return Ok(());
+        }
+        self.flush()?;
+        self.file.set_len(new_len)?;
+        let mut map = Self::map_file(self.file, self.mode())?;
+        if let (Mapping::CopyOnWrite(ref mut new), Mapping::CopyOnWrite(ref old)) =
+            (&mut map, &self.map)
+        {
+            new[..old_len].copy_from_slice(old);
+        }
+        self.map = map;
+        Ok(())
+    }
+
+    /// Returns the path of the mapped file.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.file.path()
+    }
+
+    /// Returns the length of the map in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Checks if the map is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+}
+
+impl Deref for MappedTempFile<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for MappedTempFile<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
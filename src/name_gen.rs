@@ -0,0 +1,233 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::global_consts::NAME_CHARS;
+use crate::helpers::fill_random;
+
+/// A source of names for temporary files and directories.
+///
+/// Builders place the generated name between their prefix and suffix, and the `*_with` constructors of `TempFile` and `TempDir`
+/// use it as the whole name. A name which is already taken is skipped by generating another one.
+pub trait NameGenerator: Debug + Send + Sync {
+    /// Generates the next name.
+    ///
+    /// `len` is the requested length; generators with a fixed format, such as UUIDs, may ignore it.
+    fn generate(&self, len: usize) -> String;
+}
+
+/// Builds a name of `len` characters, choosing each from four random bytes supplied by `fill`.
+fn chars_from_random(len: usize, mut fill: impl FnMut(&mut [u8])) -> String {
+    let chars = NAME_CHARS;
+    let mut bytes = vec![0u8; len * 4];
+    fill(&mut bytes);
+    bytes
+        .chunks_exact(4)
+        .map(|c| {
+            let n = u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as usize;
+            chars[n % chars.len()] as char
+        })
+        .collect()
+}
+
+/// Generates names of random characters the same way `TempFile::new_random` and `TempDir::new_random` do.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultNames;
+
+impl NameGenerator for DefaultNames {
+    fn generate(&self, len: usize) -> String {
+        crate::helpers::random_name(len)
+    }
+}
+
+/// Generates a reproducible sequence of random-looking names from a seed, for tests which need stable temporary names.
+///
+/// The sequence is produced by SplitMix64, so a given seed yields the same names on every platform and in every version of this crate.
+#[derive(Debug)]
+pub struct SeededNames {
+    /// The SplitMix64 state.
+    state: AtomicU64,
+}
+
+impl SeededNames {
+    /// The increment of the SplitMix64 state for each output.
+    const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    /// Creates a generator which produces the sequence of names determined by `seed`.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// Returns the next 64 random bits.
+    fn next_u64(&self) -> u64 {
+        let mut z = self
+            .state
+            .fetch_add(Self::GAMMA, Ordering::Relaxed)
+            .wrapping_add(Self::GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl NameGenerator for SeededNames {
+    fn generate(&self, len: usize) -> String {
+        chars_from_random(len, |buf| {
+            for chunk in buf.chunks_mut(8) {
+                chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+            }
+        })
+    }
+}
+
+/// The UUID versions [`UuidNames`] can generate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UuidVersion {
+    /// Entirely random UUIDs.
+    V4,
+    /// UUIDs which start with a millisecond timestamp, so they sort by creation time.
+    V7,
+}
+
+/// Generates hyphenated, lowercase UUIDs such as `0190b7c4-5e1a-7c3d-9f20-6a1b2c3d4e5f`.
+///
+/// The random bits come from the operating system with the `getrandom` feature and from `rand` with the `rand_gen` feature.
+/// Without either, they come from randomly keyed std hashers, which are not a cryptographically secure source: the names
+/// are still unique in practice, but do not carry the unpredictability RFC 9562 expects of version 4 UUIDs.
+#[derive(Debug, Clone, Copy)]
+pub struct UuidNames {
+    /// The UUID version to generate.
+    version: UuidVersion,
+}
+
+impl UuidNames {
+    /// Creates a generator of random (version 4) UUIDs.
+    #[must_use]
+    pub fn v4() -> Self {
+        Self {
+            version: UuidVersion::V4,
+        }
+    }
+
+    /// Creates a generator of time-ordered (version 7) UUIDs.
+    #[must_use]
+    pub fn v7() -> Self {
+        Self {
+            version: UuidVersion::V7,
+        }
+    }
+}
+
+impl NameGenerator for UuidNames {
+    fn generate(&self, _len: usize) -> String {
+        let mut bytes = [0u8; 16];
+        fill_random(&mut bytes);
+        let version = match self.version {
+            UuidVersion::V4 => 0x40,
+            UuidVersion::V7 => {
+                let millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis());
+                bytes[..6].copy_from_slice(&millis.to_be_bytes()[10..]);
+                0x70
+            }
+        };
+        bytes[6] = (bytes[6] & 0x0F) | version;
+        bytes[8] = (bytes[8] & 0x3F) | 0x80;
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+/// Generates names of the form `{microseconds since the epoch}-{counter}` in hexadecimal, which never repeat within the generator
+/// and sort by creation time.
+///
+/// The timestamp never goes backwards, even if the system clock does.
+#[derive(Debug, Default)]
+pub struct TimestampNames {
+    /// The latest timestamp used, in microseconds.
+    last: AtomicU64,
+    /// The number of names generated so far.
+    counter: AtomicU64,
+}
+
+impl TimestampNames {
+    /// Creates a new generator.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl NameGenerator for TimestampNames {
+    fn generate(&self, _len: usize) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| u64::try_from(d.as_micros()).unwrap_or(u64::MAX));
+        let micros = self.last.fetch_max(now, Ordering::Relaxed).max(now);
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!("{micros:013x}-{count:04x}")
+    }
+}
+
+/// Generates the names `0`, `1`, `2`, ... zero-padded to the requested length, for fully predictable output.
+#[derive(Debug, Default)]
+pub struct SequentialNames {
+    /// The next number to use.
+    next: AtomicU64,
+}
+
+impl SequentialNames {
+    /// Creates a generator which starts counting at zero.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a generator which starts counting at `start`.
+    #[must_use]
+    pub fn starting_at(start: u64) -> Self {
+        Self {
+            next: AtomicU64::new(start),
+        }
+    }
+}
+
+impl NameGenerator for SequentialNames {
+    fn generate(&self, len: usize) -> String {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        format!("{n:0len$}")
+    }
+}
+
+/// Generates names of random characters drawn directly from the operating system's random source, without the `rand` crate.
+///
+/// It never falls back to a non-cryptographic source, so a name from it is always as unpredictable as the operating system's.
+///
+/// # Panics
+///
+/// `generate` panics if the operating system's random source fails.
+#[cfg(feature = "getrandom")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsRandomNames;
+
+#[cfg(feature = "getrandom")]
+impl NameGenerator for OsRandomNames {
+    fn generate(&self, len: usize) -> String {
+        chars_from_random(len, |buf| {
+            if let Err(e) = getrandom::fill(buf) {
+                panic!("OsRandomNames: the operating system's random source failed: {e}");
+            }
+        })
+    }
+}
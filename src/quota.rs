@@ -0,0 +1,167 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::error::{TempError, TempResult};
+
+/// An aggregate byte limit shared by the files a `TempDir` creates.
+///
+/// The quota of a subdirectory is chained to that of its parent, so everything charged to it is charged to every ancestor as well.
+#[derive(Debug)]
+pub(crate) struct DirQuota {
+    /// The maximum total size of the files, in bytes.
+    limit: AtomicU64,
+    /// The total size currently charged to the quota, in bytes.
+    used: AtomicU64,
+    /// The quota of the parent directory, if it has one.
+    parent: Option<Arc<DirQuota>>,
+}
+
+impl DirQuota {
+    /// Creates a quota with the given limit and nothing charged to it, chained to the quota of the parent directory if any.
+    pub(crate) fn new(limit: u64, parent: Option<Arc<DirQuota>>) -> Arc<Self> {
+        Arc::new(Self {
+            limit: AtomicU64::new(limit),
+            used: AtomicU64::new(0),
+            parent,
+        })
+    }
+
+    /// Returns the maximum total size of the files, in bytes.
+    pub(crate) fn limit(&self) -> u64 {
+        self.limit.load(Ordering::SeqCst)
+    }
+
+    /// Changes the maximum total size of the files. Files already over the new limit are not truncated.
+    pub(crate) fn set_limit(&self, limit: u64) {
+        self.limit.store(limit, Ordering::SeqCst);
+    }
+
+    /// Returns the total size currently charged to the quota, in bytes.
+    pub(crate) fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Charges `delta` bytes to the quota and its ancestors, failing without charging any of them if that would exceed a limit.
+    fn charge(&self, delta: u64) -> TempResult<()> {
+        let limit = self.limit();
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(delta).filter(|next| *next <= limit)
+            })
+            .map_err(|used| TempError::QuotaExceeded {
+                limit,
+                attempted: used.saturating_add(delta),
+            })?;
+        if let Some(ref parent) = self.parent {
+            if let Err(e) = parent.charge(delta) {
+                self.used.fetch_sub(delta, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Charges `delta` bytes to the quota and its ancestors regardless of their limits.
+    fn force_charge(&self, delta: u64) {
+        self.used.fetch_add(delta, Ordering::SeqCst);
+        if let Some(ref parent) = self.parent {
+            parent.force_charge(delta);
+        }
+    }
+
+    /// Returns `delta` bytes to the quota and its ancestors.
+    fn release(&self, delta: u64) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used.saturating_sub(delta))
+            });
+        if let Some(ref parent) = self.parent {
+            parent.release(delta);
+        }
+    }
+}
+
+/// The byte limits a `TempFile` is subject to: its own, and that of the `TempDir` which created it.
+#[derive(Debug, Default)]
+pub(crate) struct FileQuota {
+    /// The maximum size of the file, in bytes.
+    limit: Option<u64>,
+    /// The aggregate quota of the directory which created the file.
+    dir: Option<Arc<DirQuota>>,
+    /// The size of the file currently charged to the directory's quota, in bytes.
+    charged: AtomicU64,
+}
+
+impl FileQuota {
+    /// Checks if any limit applies, i.e. if sizes have to be checked at all.
+    pub(crate) fn is_active(&self) -> bool {
+        self.limit.is_some() || self.dir.is_some()
+    }
+
+    /// Returns the maximum size of the file, in bytes.
+    pub(crate) fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Sets the maximum size of the file, in bytes.
+    pub(crate) fn set_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit;
+    }
+
+    /// Subjects the file to a directory's quota, charging its current size to it regardless of the limit.
+    pub(crate) fn attach(&mut self, dir: Arc<DirQuota>, size: u64) {
+        self.detach();
+        dir.force_charge(size);
+        self.charged.store(size, Ordering::SeqCst);
+        self.dir = Some(dir);
+    }
+
+    /// Releases the file from its directory's quota, if any.
+    fn detach(&mut self) {
+        if let Some(dir) = self.dir.take() {
+            dir.release(self.charged.swap(0, Ordering::SeqCst));
+        }
+    }
+
+    /// Checks that the file may grow to `new_size` bytes, charging the growth to the directory's quota.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::QuotaExceeded)` if either limit would be exceeded.
+    pub(crate) fn reserve(&self, new_size: u64) -> TempResult<()> {
+        if let Some(limit) = self.limit {
+            if new_size > limit {
+                return Err(TempError::QuotaExceeded {
+                    limit,
+                    attempted: new_size,
+                });
+            }
+        }
+        if let Some(ref dir) = self.dir {
+            let charged = self.charged.load(Ordering::SeqCst);
+            if new_size > charged {
+                dir.charge(new_size - charged)?;
+                self.charged.store(new_size, Ordering::SeqCst);
+            }
+        }
+        Ok(())
+    }
+
+    /// Records that the file is now `size` bytes long, returning any space it no longer uses to the directory's quota.
+    pub(crate) fn settle(&self, size: u64) {
+        if let Some(ref dir) = self.dir {
+            let charged = self.charged.load(Ordering::SeqCst);
+            if size < charged {
+                dir.release(charged - size);
+                self.charged.store(size, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+impl Drop for FileQuota {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
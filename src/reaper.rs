@@ -0,0 +1,287 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime};
+
+use crate::cleanup::{remove_dir_reporting, CleanupReport};
+use crate::error::{IoResultExt, Operation, TempResult};
+
+/// Name of the file which records the owning process of a temporary directory.
+pub const OWNER_MARKER: &str = ".tempfs-owner";
+
+/// Prefix of the hidden staging files written while persisting, which a [`Reaper`] removes once their creator is gone.
+pub const STAGING_PREFIX: &str = ".tempfs-stage-";
+
+/// Writes an owner marker into `dir`, recording the current process and boot so that a [`Reaper`] can later tell whether the directory was orphaned.
+///
+/// # Arguments
+///
+/// * `dir` - The directory to mark.
+///
+/// # Errors
+///
+/// Returns an error if the marker cannot be written.
+pub fn write_owner_marker<P: AsRef<Path>>(dir: P) -> io::Result<()> {
+    let mut contents = format!("{}\n", process::id());
+    if let Some(boot_id) = boot_id() {
+        contents.push_str(&boot_id);
+        contents.push('\n');
+    }
+    let mut file = fs::File::create(dir.as_ref().join(OWNER_MARKER))?;
+    file.write_all(contents.as_bytes())
+}
+
+/// Returns an identifier of the current boot, used to tell PIDs from before a reboot apart from live ones.
+fn boot_id() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        fs::read_to_string("/proc/sys/kernel/random/boot_id")
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// The process which created a temporary entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Owner {
+    /// The PID of the process.
+    pid: u32,
+    /// The boot the process ran in, if known.
+    boot_id: Option<String>,
+}
+
+impl Owner {
+    /// Parses the contents of an owner marker.
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        let pid = lines.next()?.trim().parse().ok()?;
+        let boot_id = lines
+            .next()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty());
+        Some(Self { pid, boot_id })
+    }
+
+    /// Parses the PID embedded in the name of a hidden staging file, of the form `.tempfs-stage-{name}.{pid}-{counter}.tmp`.
+    ///
+    /// Names without the [`STAGING_PREFIX`] are not staging files, however similar they look.
+    fn from_staging_name(name: &OsStr) -> Option<Self> {
+        let name = name
+            .to_str()?
+            .strip_prefix(STAGING_PREFIX)?
+            .strip_suffix(".tmp")?;
+        let (_, tag) = name.rsplit_once('.')?;
+        let (pid, counter) = tag.split_once('-')?;
+        counter.parse::<u64>().ok()?;
+        Some(Self {
+            pid: pid.parse().ok()?,
+            boot_id: None,
+        })
+    }
+
+    /// Checks if the owner is the current process.
+    fn is_current(&self) -> bool {
+        self.pid == process::id() && self.same_boot()
+    }
+
+    /// Checks if the owner ran in the current boot, assuming so when either boot is unknown.
+    fn same_boot(&self) -> bool {
+        match (&self.boot_id, boot_id()) {
+            (Some(owner), Some(current)) => *owner == current,
+            _ => true,
+        }
+    }
+
+    /// Checks if the owning process is still running. On non-Unix platforms processes are assumed to be alive.
+    fn is_alive(&self) -> bool {
+        if !self.same_boot() {
+            return false;
+        }
+        #[cfg(unix)]
+        {
+            let Ok(pid) = libc::pid_t::try_from(self.pid) else {
+                return false;
+            };
+            // SAFETY: Signal 0 performs only the existence and permission checks; nothing is delivered.
+            if unsafe { libc::kill(pid, 0) } == 0 {
+                return true;
+            }
+            io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+        }
+        #[cfg(not(unix))]
+        {
+            true
+        }
+    }
+}
+
+/// Why an entry was reaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReapReason {
+    /// The process which created the entry is no longer running.
+    OwnerGone,
+    /// The entry is older than the reaper's time to live.
+    Expired,
+}
+
+/// An entry removed, or in dry-run mode one which would be removed, by a [`Reaper`].
+#[derive(Debug, Clone)]
+pub struct ReapedEntry {
+    /// The path of the entry.
+    pub path: PathBuf,
+    /// Why the entry was reaped.
+    pub reason: ReapReason,
+}
+
+/// The outcome of a [`Reaper`] run.
+#[derive(Debug, Default)]
+pub struct ReapReport {
+    /// The entries which were removed, or would be removed in dry-run mode.
+    reaped: Vec<ReapedEntry>,
+    /// The entries which could not be removed.
+    failures: CleanupReport,
+}
+
+impl ReapReport {
+    /// Returns the entries which were removed, or would be removed in dry-run mode.
+    #[must_use]
+    pub fn reaped(&self) -> &[ReapedEntry] {
+        &self.reaped
+    }
+
+    /// Returns the entries which could not be removed.
+    #[must_use]
+    pub fn failures(&self) -> &CleanupReport {
+        &self.failures
+    }
+}
+
+/// Removes temporary entries left behind in a base directory by processes which crashed or were killed.
+///
+/// Only entries recognisably created by tempfs are considered: directories carrying an [`OWNER_MARKER`]
+/// (see [`write_owner_marker`] and `TempDirBuilder::owner_marker`) and the hidden staging files used for persisting,
+/// whose names start with [`STAGING_PREFIX`] and embed the PID of their creator. Entries owned by the current process are never reaped.
+///
+/// Directories created by `TempDir::new`, `TempDir::new_random` and the other constructors carry no marker, so they are never
+/// eligible, even with a `ttl`. Build them with `TempDirBuilder::owner_marker(true)`, or call `TempDir::mark_owner`, to opt in.
+#[derive(Debug, Clone)]
+pub struct Reaper {
+    /// The directory to scan.
+    base: PathBuf,
+    /// Age after which entries are reaped even if their owner is alive, or `None` to only reap orphans.
+    ttl: Option<Duration>,
+    /// Whether to only report what would be removed.
+    dry_run: bool,
+}
+
+impl Reaper {
+    /// Creates a reaper which scans the given base directory and only reaps entries whose owner is gone.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The directory to scan, e.g. the system temporary directory.
+    pub fn new<P: AsRef<Path>>(base: P) -> Self {
+        Self {
+            base: base.as_ref().to_path_buf(),
+            ttl: None,
+            dry_run: false,
+        }
+    }
+
+    /// Also reaps entries older than `ttl`, even if their owning process is still running.
+    pub fn ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets whether the reaper only reports what it would remove instead of removing it.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Scans the base directory once, removing every stale entry.
+    ///
+    /// Removal continues past entries which fail; they are listed in the report's failures.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base directory cannot be listed.
+    pub fn reap(&self) -> TempResult<ReapReport> {
+        let mut report = ReapReport::default();
+        let now = SystemTime::now();
+        for entry in fs::read_dir(&self.base).context(Operation::ReadDir, &self.base)? {
+            let Ok(entry) = entry else { continue };
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            let candidate = if file_type.is_dir() {
+                let marker = path.join(OWNER_MARKER);
+                fs::read_to_string(&marker)
+                    .ok()
+                    .and_then(|c| Owner::parse(&c))
+                    .map(|owner| (owner, fs::symlink_metadata(&marker)))
+            } else if file_type.is_file() {
+                Owner::from_staging_name(&entry.file_name())
+                    .map(|owner| (owner, fs::symlink_metadata(&path)))
+            } else {
+                None
+            };
+            let Some((owner, meta)) = candidate else {
+                continue;
+            };
+            let Some(reason) = self.reason(&owner, meta.and_then(|m| m.modified()).ok(), now)
+            else {
+                continue;
+            };
+            if !self.dry_run && !remove_reporting(&path, file_type.is_dir(), &mut report.failures) {
+                continue;
+            }
+            report.reaped.push(ReapedEntry { path, reason });
+        }
+        Ok(report)
+    }
+
+    /// Decides whether an entry is stale, given its owner and modification time.
+    fn reason(
+        &self,
+        owner: &Owner,
+        modified: Option<SystemTime>,
+        now: SystemTime,
+    ) -> Option<ReapReason> {
+        if owner.is_current() {
+            return None;
+        }
+        if !owner.is_alive() {
+            return Some(ReapReason::OwnerGone);
+        }
+        let age = now.duration_since(modified?).ok()?;
+        self.ttl
+            .filter(|ttl| age > *ttl)
+            .map(|_| ReapReason::Expired)
+    }
+}
+
+/// Removes a stale entry, recording failures in `failures` and returning whether it is gone.
+fn remove_reporting(path: &Path, is_dir: bool, failures: &mut CleanupReport) -> bool {
+    let before = failures.failures().len();
+    if is_dir {
+        if let Err(e) = remove_dir_reporting(path, failures) {
+            failures.push(path, e);
+        }
+    } else {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => failures.push(path, e),
+            _ => {}
+        }
+    }
+    failures.failures().len() == before
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::cleanup::{remove_dir_and_parents, remove_file_and_parents, CleanupReport};
+
+/// Whether newly created temporary objects are recorded in the registry.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Source of registry entry identifiers.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The paths of live temporary objects, keyed by registry entry identifier.
+static ENTRIES: Mutex<BTreeMap<usize, Entry>> = Mutex::new(BTreeMap::new());
+
+/// A live temporary object in the registry.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// The path of the object.
+    path: PathBuf,
+    /// Whether `path` is a directory which is removed recursively.
+    is_dir: bool,
+    /// The first parent directory created for the object, removed along with it if it is left empty.
+    created_parent: Option<PathBuf>,
+}
+
+/// Locks the registry, ignoring poisoning so cleanup still works after a panic.
+fn entries() -> MutexGuard<'static, BTreeMap<usize, Entry>> {
+    ENTRIES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Enables the process-wide registry of live temporary objects.
+///
+/// Every `TempFile` and `TempDir` created afterwards records its path until it is dropped, persisted or otherwise disarmed,
+/// so that [`cleanup_registered`] can remove it even when `Drop` never runs.
+pub fn enable_registry() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Checks if the process-wide registry of live temporary objects is enabled.
+#[must_use]
+pub fn is_registry_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Records a live temporary object if the registry is enabled, returning its entry identifier.
+pub(crate) fn register(path: &Path, is_dir: bool, created_parent: Option<&Path>) -> Option<usize> {
+    if !is_registry_enabled() {
+        return None;
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    entries().insert(
+        id,
+        Entry {
+            path: path.to_path_buf(),
+            is_dir,
+            created_parent: created_parent.map(Path::to_path_buf),
+        },
+    );
+    Some(id)
+}
+
+/// Changes the path recorded for a registry entry, e.g. after a rename.
+pub(crate) fn update(id: usize, path: &Path) {
+    if let Some(entry) = entries().get_mut(&id) {
+        entry.path = path.to_path_buf();
+    }
+}
+
+/// Removes a registry entry once its object no longer needs cleaning up.
+pub(crate) fn unregister(id: usize) {
+    entries().remove(&id);
+}
+
+/// Removes every temporary object in the registry, then clears it.
+///
+/// This is what the handlers installed by [`install_signal_handlers`] and [`install_exit_hook`] run, and may also be called directly.
+pub fn cleanup_registered() -> CleanupReport {
+    let drained = std::mem::take(&mut *entries());
+    let mut report = CleanupReport::default();
+    for entry in drained.into_values() {
+        let created = entry.created_parent.as_deref();
+        if entry.is_dir {
+            if let Err(e) = remove_dir_and_parents(&entry.path, created, &mut report) {
+                report.push(&entry.path, e);
+            }
+        } else if let Err((path, e)) = remove_file_and_parents(&entry.path, created) {
+            report.push(&path, e);
+        }
+    }
+    report
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+    use std::{mem, ptr, thread};
+
+    use super::{cleanup_registered, enable_registry};
+
+    /// The signals which trigger cleanup.
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// Write end of the pipe the signal handler forwards signal numbers through, or -1 before installation.
+    static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    /// Whether the signal handlers have been installed.
+    static SIGNALS_INSTALLED: Mutex<bool> = Mutex::new(false);
+
+    /// Whether the exit hook has been installed.
+    static EXIT_HOOK_INSTALLED: Mutex<bool> = Mutex::new(false);
+
+    /// Signal handler which forwards the signal number to the watcher thread.
+    ///
+    /// Only `write(2)` is called here, as it is async-signal-safe, unlike allocating or locking.
+    extern "C" fn forward_signal(signo: libc::c_int) {
+        let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+        if fd >= 0 {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let byte = signo as u8;
+            // SAFETY: `byte` is valid for a one byte read for the duration of the call.
+            unsafe {
+                libc::write(fd, ptr::addr_of!(byte).cast(), 1);
+            }
+        }
+    }
+
+    /// Waits for a forwarded signal, cleans up the registry, then re-raises the signal with its default disposition.
+    fn watch_signals(read_fd: libc::c_int) {
+        let mut byte = 0u8;
+        loop {
+            // SAFETY: `byte` is valid for a one byte write for the duration of the call.
+            let n = unsafe { libc::read(read_fd, ptr::addr_of_mut!(byte).cast(), 1) };
+            if n == 1 {
+                break;
+            }
+            if n < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return;
+        }
+        let signo = libc::c_int::from(byte);
+        let _ = cleanup_registered();
+        // SAFETY: Restoring the default disposition and raising a signal have no memory safety requirements.
+        unsafe {
+            libc::signal(signo, libc::SIG_DFL);
+            libc::raise(signo);
+        }
+    }
+
+    /// Creates a close-on-exec pipe, returning its read and write ends.
+    fn cloexec_pipe() -> io::Result<(libc::c_int, libc::c_int)> {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two descriptors `pipe` writes.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in fds {
+            // SAFETY: `fd` was just returned by `pipe`.
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        Ok((fds[0], fds[1]))
+    }
+
+    /// Installs handlers for `SIGINT`, `SIGTERM` and `SIGHUP` which remove every registered temporary object and then re-raise the signal.
+    ///
+    /// This enables the registry. Cleanup runs on a dedicated thread, since very little may be done safely inside a signal handler.
+    /// Any handlers previously installed for these signals are replaced. Installing more than once has no further effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pipe, the watcher thread, or a handler cannot be created.
+    pub fn install_signal_handlers() -> io::Result<()> {
+        enable_registry();
+        let mut installed = SIGNALS_INSTALLED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if *installed {
+            return Ok(());
+        }
+        let (read_fd, write_fd) = cloexec_pipe()?;
+        thread::Builder::new()
+            .name("tempfs-signals".to_string())
+            .spawn(move || watch_signals(read_fd))?;
+        SIGNAL_PIPE.store(write_fd, Ordering::SeqCst);
+        for signo in SIGNALS {
+            // SAFETY: A zeroed `sigaction` is a valid starting point, and every field used is initialised below.
+            let mut action: libc::sigaction = unsafe { mem::zeroed() };
+            action.sa_sigaction =
+                forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            // SAFETY: `action` is a valid `sigaction` and the previous action is not requested.
+            let ret = unsafe {
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signo, &action, ptr::null_mut())
+            };
+            if ret != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        *installed = true;
+        Ok(())
+    }
+
+    /// Exit handler which removes every registered temporary object.
+    extern "C" fn cleanup_at_exit() {
+        let _ = cleanup_registered();
+    }
+
+    /// Registers an `atexit` handler which removes every registered temporary object when the process exits,
+    /// including through `std::process::exit`, where `Drop` does not run.
+    ///
+    /// This enables the registry. Installing more than once has no further effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handler cannot be registered.
+    pub fn install_exit_hook() -> io::Result<()> {
+        enable_registry();
+        let mut installed = EXIT_HOOK_INSTALLED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if *installed {
+            return Ok(());
+        }
+        // SAFETY: `cleanup_at_exit` is a valid `extern "C"` function which lives for the whole program.
+        if unsafe { libc::atexit(cleanup_at_exit) } != 0 {
+            return Err(io::Error::other("Could not register exit handler"));
+        }
+        *installed = true;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{install_exit_hook, install_signal_handlers};
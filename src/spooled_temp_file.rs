@@ -0,0 +1,164 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::TempResult;
+use crate::temp_file::TempFile;
+
+/// Where the contents of a `SpooledTempFile` currently live.
+#[derive(Debug)]
+enum SpooledData {
+    /// The contents are held in memory.
+    InMemory(Cursor<Vec<u8>>),
+    /// The contents have been rolled over to a temporary file.
+    OnDisk(TempFile),
+}
+
+/// A temporary file which is kept in memory until it grows past a size threshold, then transparently rolls over to a `TempFile`.
+///
+/// This avoids touching the disk for the common case of small temporary data while still supporting large contents.
+/// On Unix the rolled-over file is anonymous (see [`TempFile::anonymous`]), so it never has a visible name.
+#[derive(Debug)]
+pub struct SpooledTempFile {
+    /// The size in bytes past which the contents are rolled over to disk.
+    max_size: usize,
+    /// The directory the file is created in on rollover, or `None` for the base directory of the current `TempConfig`.
+    dir: Option<PathBuf>,
+    /// The current contents.
+    data: SpooledData,
+}
+
+impl SpooledTempFile {
+    /// Creates a new, empty spooled temporary file which rolls over to the base directory of the current `TempConfig` (the
+    /// system temporary directory by default), as it is at the time of the rollover.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - The size in bytes past which the contents are moved to disk.
+    #[must_use]
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            dir: None,
+            data: SpooledData::InMemory(Cursor::new(Vec::new())),
+        }
+    }
+
+    /// Creates a new, empty spooled temporary file which rolls over to the given directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - The size in bytes past which the contents are moved to disk.
+    /// * `dir` - The directory to create the file in on rollover. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig` at the time of the rollover.
+    #[must_use]
+    pub fn new_in<P: AsRef<Path>>(max_size: usize, dir: P) -> Self {
+        Self {
+            max_size,
+            dir: Some(dir.as_ref().to_path_buf()),
+            data: SpooledData::InMemory(Cursor::new(Vec::new())),
+        }
+    }
+
+    /// Checks if the contents have been rolled over to disk.
+    #[must_use]
+    pub fn is_rolled(&self) -> bool {
+        matches!(self.data, SpooledData::OnDisk(_))
+    }
+
+    /// Moves the contents to a temporary file on disk, keeping the current position. Does nothing if already rolled over.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written to.
+    pub fn rollover(&mut self) -> TempResult<()> {
+        let SpooledData::InMemory(ref cursor) = self.data else {
+            return Ok(());
+        };
+        let mut file = self.create_backing_file()?;
+        file.write_all(cursor.get_ref())?;
+        file.seek(SeekFrom::Start(cursor.position()))?;
+        self.data = SpooledData::OnDisk(file);
+        Ok(())
+    }
+
+    /// Creates the file the contents are rolled over to.
+    #[cfg(unix)]
+    fn create_backing_file(&self) -> TempResult<TempFile> {
+        match self.dir {
+            Some(ref dir) => TempFile::anonymous(dir),
+            None => TempFile::anonymous(crate::config::base_dir()?),
+        }
+    }
+
+    /// Creates the file the contents are rolled over to.
+    #[cfg(not(unix))]
+    fn create_backing_file(&self) -> TempResult<TempFile> {
+        let mut builder = crate::builder::TempFileBuilder::new();
+        if let Some(ref dir) = self.dir {
+            builder.dir(dir);
+        }
+        builder.build()
+    }
+
+    /// Returns the in-memory contents, or `None` if they have been rolled over to disk.
+    #[must_use]
+    pub fn in_memory(&self) -> Option<&[u8]> {
+        match self.data {
+            SpooledData::InMemory(ref cursor) => Some(cursor.get_ref()),
+            SpooledData::OnDisk(_) => None,
+        }
+    }
+
+    /// Rolls the contents over to disk if needed and returns the underlying `TempFile`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if rolling over fails.
+    pub fn into_temp_file(mut self) -> TempResult<TempFile> {
+        self.rollover()?;
+        match self.data {
+            SpooledData::OnDisk(file) => Ok(file),
+            SpooledData::InMemory(_) => unreachable!("rollover always moves the contents to disk"),
+        }
+    }
+}
+
+impl Write for SpooledTempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let SpooledData::InMemory(ref cursor) = self.data {
+            let end = usize::try_from(cursor.position())
+                .unwrap_or(usize::MAX)
+                .saturating_add(buf.len());
+            if end > self.max_size {
+                self.rollover().map_err(io::Error::from)?;
+            }
+        }
+        match self.data {
+            SpooledData::InMemory(ref mut cursor) => cursor.write(buf),
+            SpooledData::OnDisk(ref mut file) => file.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self.data {
+            SpooledData::InMemory(ref mut cursor) => cursor.flush(),
+            SpooledData::OnDisk(ref mut file) => file.flush(),
+        }
+    }
+}
+
+impl Read for SpooledTempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.data {
+            SpooledData::InMemory(ref mut cursor) => cursor.read(buf),
+            SpooledData::OnDisk(ref mut file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for SpooledTempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self.data {
+            SpooledData::InMemory(ref mut cursor) => cursor.seek(pos),
+            SpooledData::OnDisk(ref mut file) => file.seek(pos),
+        }
+    }
+}
@@ -1,41 +1,78 @@
-#[cfg(feature = "rand_gen")]
-use crate::global_consts::{num_retry, rand_fn_len, valid_chars};
-#[cfg(feature = "rand_gen")]
-use rand::Rng;
 #[cfg(feature = "regex_support")]
 use regex::Regex;
 use std::env;
+use std::ffi::OsStr;
 use std::fs;
-#[cfg(unix)]
-use std::fs::Permissions;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
 
-use crate::error::TempResult;
-use crate::helpers::normalize_path;
+use crate::cleanup::{
+    remove_dir_and_parents, remove_empty_parents, report_drop_failure, CleanupReport,
+};
+use crate::config::{CleanupPolicy, TempConfig};
+use crate::error::{IoResultExt, Operation, TempError, TempResult};
+use crate::global_consts::KEEP_ENV_VAR;
+use crate::helpers::{join_within, join_within_lexically, normalize_path};
+use crate::location::TempLocation;
+#[cfg(unix)]
+use crate::lock::LockFile;
+use crate::name_gen::NameGenerator;
+use crate::quota::DirQuota;
 use crate::temp_file::TempFile;
 
+/// A hierarchical listing of the files and subdirectories managed by a `TempDir`, as returned by [`TempDir::list_tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirTree<'a> {
+    /// The path of the directory.
+    pub path: &'a Path,
+    /// The paths of the files managed by the directory.
+    pub files: Vec<&'a Path>,
+    /// The subdirectories managed by the directory.
+    pub dirs: Vec<DirTree<'a>>,
+}
+
+/// An entry found on disk in a `TempDir`, as returned by [`TempDir::entries`].
+#[derive(Debug, Clone)]
+pub struct TempEntry {
+    /// The path of the entry.
+    pub path: PathBuf,
+    /// The metadata of the entry, without following symlinks.
+    pub metadata: fs::Metadata,
+    /// Whether the entry is tracked by the `TempDir`.
+    pub tracked: bool,
+}
+
 /// A temporary directory that automatically cleans up its contents when dropped.
 ///
-/// Files created through the `TempDir` are tracked and removed upon drop.
+/// Files and subdirectories created through the `TempDir` are tracked and removed upon drop.
 #[derive(Debug)]
 pub struct TempDir {
     /// The full path to the temporary directory.
     path: Option<PathBuf>,
     /// Temporary files contained within the directory.
     files: Vec<TempFile>,
+    /// Temporary subdirectories contained within the directory.
+    dirs: Vec<TempDir>,
     /// The first created parent directory of the parent directories.
     created_parent: Option<PathBuf>,
+    /// The entry recording the directory in the process-wide registry, if it is enabled.
+    registry_id: Option<usize>,
+    /// The aggregate byte limit of the files created through the directory, if one has been set.
+    quota: Option<Arc<DirQuota>>,
+    /// When the directory is kept instead of removed on drop.
+    keep: CleanupPolicy,
 }
 
 impl TempDir {
     /// Creates a new temporary directory at the specified path.
     ///
-    /// The directory (and any missing parent directories) will be created.
+    /// The directory (and any missing parent directories) will be created with the directory mode of the current `TempConfig` (0o700 by default) on Unix.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path at which to create the directory. If a relative path is provided, it is resolved relative to the system temporary directory.
+    /// * `path` - The path at which to create the directory. If a relative path is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
     ///
     /// # Errors
     ///
@@ -45,19 +82,36 @@ impl TempDir {
         let path_buf = if path_ref.is_absolute() {
             path_ref
         } else {
-            env::temp_dir().join(path_ref)
+            crate::config::base_dir()?.join(path_ref)
         };
-        let created = Self::create_with_parent(&path_buf)?;
-        Ok(Self {
-            path: Some(path_buf),
-            files: Vec::new(),
-            created_parent: created,
-        })
+        Self::create(path_buf, TempConfig::current().dir_mode, false)
+    }
+
+    /// Creates a new temporary directory at the specified path within a storage location.
+    ///
+    /// The directory (and any missing parent directories) will be created with the directory mode of the current `TempConfig` (0o700 by default) on Unix.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - Where to place the directory if `path` is relative.
+    /// * `path` - The path at which to create the directory. If a relative path is provided, it is resolved relative to the directory `location` resolves to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the location cannot be resolved or if the directory cannot be created.
+    pub fn new_at<P: AsRef<Path>>(location: &TempLocation, path: P) -> TempResult<Self> {
+        let path_ref = normalize_path(path.as_ref());
+        let path_buf = if path_ref.is_absolute() {
+            path_ref
+        } else {
+            location.resolve()?.join(path_ref)
+        };
+        Self::create(path_buf, TempConfig::current().dir_mode, false)
     }
 
     /// Creates a new temporary directory at the specified path.
     ///
-    /// The directory (and any missing parent directories) will be created.
+    /// The directory (and any missing parent directories) will be created with the directory mode of the current `TempConfig` (0o700 by default) on Unix.
     ///
     /// # Arguments
     ///
@@ -76,82 +130,146 @@ impl TempDir {
         Self::new(path_buf)
     }
 
-    #[cfg(feature = "rand_gen")]
+    /// Creates a new temporary directory at the specified path with the given permission mode.
+    ///
+    /// The mode is applied to the directory and any missing parent directories as they are created, and is subject to the umask.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path at which to create the directory. If a relative path is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
+    /// * `mode` - The permission mode of the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created.
+    #[cfg(unix)]
+    pub fn new_with_mode<P: AsRef<Path>>(path: P, mode: u32) -> TempResult<Self> {
+        let path_ref = normalize_path(path.as_ref());
+        let path_buf = if path_ref.is_absolute() {
+            path_ref
+        } else {
+            crate::config::base_dir()?.join(path_ref)
+        };
+        Self::create(path_buf, mode, false)
+    }
+
     /// Creates a new temporary directory with a random name in the given parent directory.
     ///
-    /// The directory name will consist of alphanumeric characters only, ensuring compatibility
-    /// across different filesystems.
+    /// The name comes from the `NameGenerator` of the current `TempConfig`, random alphanumeric characters by default.
     ///
     /// # Arguments
     ///
-    /// * `dir` - An optional parent directory in which to create the temporary directory. If a relative directory is provided, it is resolved relative to the system temporary directory.
+    /// * `dir` - An optional parent directory in which to create the temporary directory. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
     ///
     /// # Errors
     ///
-    /// Returns an error if a unique directory name cannot be generated or if directory creation fails.
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if directory creation fails.
     pub fn new_random<P: AsRef<Path>>(dir: Option<P>) -> TempResult<Self> {
         let parent_dir = if let Some(d) = dir {
             let d_ref = normalize_path(d.as_ref());
             if d_ref.is_absolute() {
                 d_ref
             } else {
-                env::temp_dir().join(d_ref)
+                crate::config::base_dir()?.join(d_ref)
             }
         } else {
-            env::temp_dir()
+            crate::config::base_dir()?
         };
-        let mut rng = rand::rng();
-
-        for _ in 0..num_retry() {
-            let name: String = (0..rand_fn_len())
-                .map(|_| {
-                    let idx = rng.random_range(0..valid_chars().len());
-                    valid_chars()[idx] as char
-                })
-                .collect();
-
-            let full_path = parent_dir.join(&name);
-            if !full_path.exists() {
-                let created = Self::create_with_parent(&full_path)?;
-                return Ok(Self {
-                    path: Some(full_path),
-                    files: Vec::new(),
-                    created_parent: created,
-                });
-            }
-        }
-        Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            "Could not generate a unique directory name",
+        let config = TempConfig::current();
+        crate::builder::create_unique(
+            &parent_dir,
+            "",
+            "",
+            config.name_len,
+            config.retries,
+            &*config.names,
+            |path| Self::create(path, config.dir_mode, true),
         )
-        .into())
     }
 
-    /// Function to create the directory and its parent directories, then set their permissions to rwx------, returning the first component of the parent's path which does not exist, or None if it all exists except for the child.
-    fn create_with_parent(path: &PathBuf) -> TempResult<Option<PathBuf>> {
-        #[cfg(unix)]
-        use std::os::unix::fs::PermissionsExt;
+    /// Creates a new temporary directory in the given parent directory, named by a `NameGenerator`.
+    ///
+    /// A name which is already taken is skipped by generating another one.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - An optional parent directory in which to create the temporary directory. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
+    /// * `names` - The generator of the directory name, such as `SeededNames` for reproducible names.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if directory creation fails.
+    pub fn new_random_with<P: AsRef<Path>>(
+        dir: Option<P>,
+        names: &dyn NameGenerator,
+    ) -> TempResult<Self> {
+        let parent_dir = match dir {
+            Some(d) => crate::config::base_dir()?.join(normalize_path(d.as_ref())),
+            None => crate::config::base_dir()?,
+        };
+        let config = TempConfig::current();
+        crate::builder::create_unique(
+            &parent_dir,
+            "",
+            "",
+            config.name_len,
+            config.retries,
+            names,
+            |path| Self::create(path, config.dir_mode, true),
+        )
+    }
+
+    /// Creates a new temporary directory at an already resolved path with the given permission mode.
+    ///
+    /// If `exclusive` is set, an existing directory at `path` is reported as `TempError::PathExists` instead of being adopted.
+    /// The cleanup policy is taken from the current `TempConfig`.
+    pub(crate) fn create(path: PathBuf, mode: u32, exclusive: bool) -> TempResult<Self> {
+        let created = Self::create_with_parent(&path, mode, exclusive)?;
+        let registry_id = crate::registry::register(&path, true, created.as_deref());
+        Ok(Self {
+            path: Some(path),
+            files: Vec::new(),
+            dirs: Vec::new(),
+            created_parent: created,
+            registry_id,
+            quota: None,
+            keep: TempConfig::current().cleanup,
+        })
+    }
+
+    /// Clears the path, disarming deletion, and removes the directory from the process-wide registry.
+    fn forget_path(&mut self) -> Option<PathBuf> {
+        if let Some(id) = self.registry_id.take() {
+            crate::registry::unregister(id);
+        }
+        self.path.take()
+    }
+
+    /// Function to create the directory and its parent directories with permission mode `mode` (rwx------ by default, subject to the umask), returning the first component of the parent's path which does not exist, or None if it all exists except for the child.
+    fn create_with_parent(
+        path: &PathBuf,
+        mode: u32,
+        exclusive: bool,
+    ) -> TempResult<Option<PathBuf>> {
         let nonexistent = crate::helpers::first_missing_directory_component(path);
-        fs::create_dir_all(path)?;
-
-        #[cfg(unix)]
-        if let Some(first_missing) = nonexistent.clone() {
-            let mut current = first_missing;
-            // Loop until the final directory in the path is reached.
-            while current != *path {
-                fs::set_permissions(&current, Permissions::from_mode(0o700))?;
-                // Append the next path component.
-                if let Some(component) = path.strip_prefix(&current).unwrap().components().next() {
-                    current = current.join(component);
-                } else {
-                    break;
+        if let Some(parent) = path.parent() {
+            crate::helpers::dir_builder(mode)
+                .recursive(true)
+                .create(parent)
+                .context(Operation::CreateParent, parent)?;
+        }
+        match crate::helpers::dir_builder(mode).create(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && !exclusive => {}
+            Err(e) => {
+                if let Some(ref c) = nonexistent {
+                    let _ = remove_empty_parents(path, c);
+                }
+                if e.kind() == io::ErrorKind::AlreadyExists {
+                    return Err(TempError::PathExists(path.clone()));
                 }
+                return Err(TempError::failed(Operation::CreateDir, path, e));
             }
-            // Finally, set permissions on the final directory.
-            fs::set_permissions(path, Permissions::from_mode(0o700))?;
-        } else {
-            // If no directory was missing (only the child directory was created)
-            fs::set_permissions(path, Permissions::from_mode(0o700))?;
         }
 
         Ok(nonexistent)
@@ -159,8 +277,7 @@ impl TempDir {
 
     /// Creates a new temporary directory with a random name in the given parent directory.
     ///
-    /// The directory name will consist of alphanumeric characters only, ensuring compatibility
-    /// across different filesystems.
+    /// The name comes from the `NameGenerator` of the current `TempConfig`, random alphanumeric characters by default.
     ///
     /// # Arguments
     ///
@@ -168,8 +285,7 @@ impl TempDir {
     ///
     /// # Errors
     ///
-    /// Returns an error if a unique directory name cannot be generated or if directory creation fails.
-    #[cfg(feature = "rand_gen")]
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if directory creation fails.
     pub fn new_random_here<P: AsRef<Path>>(dir: Option<P>) -> TempResult<Self> {
         if let Some(dir) = dir {
             let d_ref = normalize_path(dir.as_ref());
@@ -189,37 +305,399 @@ impl TempDir {
     ///
     /// # Arguments
     ///
+    /// * `filename` - The name of the file to create. It may be a relative path such as `a/b.txt`, but must stay inside the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::EscapesDirectory)` if `filename` is absolute, climbs out of the directory with `..`, or passes
+    /// through a symlink, or an error if the inner path is `None` or creation fails.
+    pub fn create_file<S: AsRef<str>>(&mut self, filename: S) -> TempResult<&mut TempFile> {
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let file_path = join_within(dir, Path::new(filename.as_ref()))?;
+        let file = TempFile::new(file_path)?;
+        Ok(self.track(file))
+    }
+
+    /// Creates a new temporary file with the given filename in the directory, without checking that it stays inside the directory.
+    ///
+    /// An absolute `filename` or one containing `..` may create (and, on drop, delete) a file anywhere. Prefer
+    /// [`create_file`](TempDir::create_file) unless `filename` is fully trusted.
+    ///
+    /// # Arguments
+    ///
     /// * `filename` - The name of the file to create.
     ///
     /// # Errors
     ///
     /// This function will return an error if the inner path is `None`.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn create_file<S: AsRef<str>>(&mut self, filename: S) -> TempResult<&mut TempFile> {
-        let dir = self.path.as_ref().ok_or_else(|| {
-            io::Error::new(io::ErrorKind::Other, "Temporary directory path is not set")
-        })?;
+    pub fn create_file_unchecked<S: AsRef<str>>(
+        &mut self,
+        filename: S,
+    ) -> TempResult<&mut TempFile> {
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
         let file_path = dir.join(filename.as_ref());
-        self.files.push(TempFile::new(file_path)?);
-        Ok(self.files.last_mut().unwrap())
+        let file = TempFile::new(file_path)?;
+        Ok(self.track(file))
     }
 
-    #[cfg(feature = "rand_gen")]
     /// Creates a new temporary file with a random name in the directory.
     ///
     /// The file is tracked and will be automatically deleted on drop.
     ///
     /// # Errors
     ///
-    /// Returns an error if a unique filename cannot be generated or if file creation fails.
-    #[allow(clippy::missing_panics_doc)]
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if file creation fails.
     pub fn create_random_file(&mut self) -> TempResult<&mut TempFile> {
-        let dir = self.path.as_ref().ok_or_else(|| {
-            io::Error::new(io::ErrorKind::Other, "Temporary directory path is not set")
-        })?;
-        self.files
-            .push(TempFile::new_random(Some(normalize_path(dir)))?);
-        Ok(self.files.last_mut().unwrap())
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let file = TempFile::new_random(Some(normalize_path(dir)))?;
+        Ok(self.track(file))
+    }
+
+    /// Creates a new temporary file in the directory, named by a `NameGenerator`.
+    ///
+    /// The file is tracked and will be automatically deleted on drop.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inner path is `None`, if a unique filename cannot be generated or if file creation fails.
+    pub fn create_random_file_with(
+        &mut self,
+        names: &dyn NameGenerator,
+    ) -> TempResult<&mut TempFile> {
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let file = TempFile::new_random_with(Some(dir), names)?;
+        Ok(self.track(file))
+    }
+
+    /// Creates a new subdirectory with the given name in the directory, along with any missing parents.
+    ///
+    /// The subdirectory is tracked and will be removed, with its contents, on drop. Files created through it count towards this directory's quota.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subdirectory, which may be a relative path such as `a/b`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::PathExists)` if the subdirectory already exists, `Err(TempError::EscapesDirectory)` if `name` leaves
+    /// the directory or passes through a symlink, or an error if the inner path is `None` or creation fails.
+    pub fn create_dir<S: AsRef<str>>(&mut self, name: S) -> TempResult<&mut TempDir> {
+        self.create_dir_with(name.as_ref(), true)
+    }
+
+    /// Creates a tracked subdirectory, adopting an existing directory unless `exclusive` is set.
+    fn create_dir_with(&mut self, name: &str, exclusive: bool) -> TempResult<&mut TempDir> {
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let path = join_within(dir, Path::new(name))?;
+        let mut child = Self::create(path, TempConfig::current().dir_mode, exclusive)?;
+        child.quota = self
+            .quota
+            .as_ref()
+            .map(|quota| DirQuota::new(u64::MAX, Some(Arc::clone(quota))));
+        self.dirs.push(child);
+        Ok(self.dirs.last_mut().unwrap())
+    }
+
+    /// Creates a new temporary file at a relative path in the directory, creating each missing parent as a tracked subdirectory.
+    ///
+    /// The file is tracked by the subdirectory containing it, and appears under it in [`list_tree`](TempDir::list_tree).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The relative path of the file, such as `a/b/c.txt`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::EscapesDirectory)` if `path` is absolute, contains `..` or passes through a symlink,
+    /// `Err(TempError::InvalidFileOrPath)` if it is empty or not valid UTF-8, or an error if the inner path is `None` or creation fails.
+    pub fn create_file_at<P: AsRef<Path>>(&mut self, path: P) -> TempResult<&mut TempFile> {
+        let path = path.as_ref();
+        let mut components = Vec::new();
+        for component in path.components() {
+            match component {
+                std::path::Component::Normal(c) => {
+                    components.push(
+                        c.to_str()
+                            .ok_or_else(|| TempError::InvalidFileOrPath(path.to_path_buf()))?,
+                    );
+                }
+                std::path::Component::CurDir => {}
+                _ => return Err(TempError::EscapesDirectory(path.to_path_buf())),
+            }
+        }
+        let (file_name, parents) = components
+            .split_last()
+            .ok_or_else(|| TempError::InvalidFileOrPath(path.to_path_buf()))?;
+        let mut dir = self;
+        for name in parents {
+            dir = dir.subdir_or_adopt(name)?;
+        }
+        dir.create_file(file_name)
+    }
+
+    /// Lists the entries currently on disk in the directory, with their metadata, whether or not they are tracked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inner path is `None` or if the directory cannot be read.
+    pub fn entries(&self) -> TempResult<Vec<TempEntry>> {
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir).context(Operation::ReadDir, dir)? {
+            let entry = entry.context(Operation::ReadDir, dir)?;
+            let path = entry.path();
+            let metadata = fs::symlink_metadata(&path).context(Operation::ReadDir, &path)?;
+            let tracked = self.tracks(&path);
+            entries.push(TempEntry {
+                path,
+                metadata,
+                tracked,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Checks if `path` is a file or subdirectory tracked anywhere in the tree of the directory.
+    fn tracks(&self, path: &Path) -> bool {
+        self.files.iter().any(|f| f.path.as_deref() == Some(path))
+            || self
+                .dirs
+                .iter()
+                .any(|d| d.path() == Some(path) || d.tracks(path))
+    }
+
+    /// Returns the file at `path` if it is tracked anywhere in the tree of the directory.
+    fn tracked_file_mut(&mut self, path: &Path) -> Option<&mut TempFile> {
+        if let Some(i) = self
+            .files
+            .iter()
+            .position(|f| f.path.as_deref() == Some(path))
+        {
+            return Some(&mut self.files[i]);
+        }
+        self.dirs.iter_mut().find_map(|d| d.tracked_file_mut(path))
+    }
+
+    /// Returns the tracked directory at `path`, which must lie inside the directory, tracking it and any missing intermediate
+    /// subdirectories if necessary.
+    ///
+    /// Directories tracked further up the tree, such as one created with `create_dir("a/b")`, are reused rather than tracked twice.
+    fn dir_for(&mut self, path: &Path) -> TempResult<&mut TempDir> {
+        if self.path.as_deref() == Some(path) {
+            return Ok(self);
+        }
+        let deepest = self
+            .dirs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| {
+                d.path()
+                    .filter(|p| path.starts_with(p))
+                    .map(|p| (i, p.components().count()))
+            })
+            .max_by_key(|&(_, depth)| depth)
+            .map(|(i, _)| i);
+        if let Some(i) = deepest {
+            return self.dirs[i].dir_for(path);
+        }
+        let root = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let name = match path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|r| r.components().next())
+        {
+            Some(std::path::Component::Normal(name)) => name
+                .to_str()
+                .ok_or_else(|| TempError::InvalidFileOrPath(path.to_path_buf()))?,
+            _ => return Err(TempError::InvalidFileOrPath(path.to_path_buf())),
+        };
+        self.subdir_or_adopt(name)?.dir_for(path)
+    }
+
+    /// Starts tracking a file which was created in the directory by other means, such as a subprocess or a move.
+    ///
+    /// Any missing intermediate subdirectories are tracked as well. If the file is already tracked, it is returned as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, either absolute or relative to the directory. It must lie inside the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::InvalidFileOrPath)` if `path` is outside the directory or is not a regular file, or an error if it cannot be opened.
+    pub fn adopt<P: AsRef<Path>>(&mut self, path: P) -> TempResult<&mut TempFile> {
+        let root = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let full = normalize_path(root.join(path.as_ref()));
+        if full == *root || !full.starts_with(root) {
+            return Err(TempError::InvalidFileOrPath(full));
+        }
+        if !fs::symlink_metadata(&full)
+            .context(Operation::Open, &full)?
+            .is_file()
+        {
+            return Err(TempError::InvalidFileOrPath(full));
+        }
+        if self.tracks(&full) {
+            return self
+                .tracked_file_mut(&full)
+                .ok_or(TempError::InvalidFileOrPath(full));
+        }
+        let parent = full
+            .parent()
+            .ok_or_else(|| TempError::InvalidFileOrPath(full.clone()))?;
+        let dir = self.dir_for(parent)?;
+        let file = TempFile::open_existing(full)?;
+        Ok(dir.track(file))
+    }
+
+    /// Returns the tracked subdirectory with the given name, adopting it from disk or creating it if it is not tracked yet.
+    fn subdir_or_adopt(&mut self, name: &str) -> TempResult<&mut TempDir> {
+        match self
+            .dirs
+            .iter()
+            .position(|d| d.path().and_then(Path::file_name) == Some(OsStr::new(name)))
+        {
+            Some(i) => Ok(&mut self.dirs[i]),
+            None => self.create_dir_with(name, false),
+        }
+    }
+
+    /// Walks the directory on disk and starts tracking every file and subdirectory in it which is not tracked yet,
+    /// such as the outputs of a subprocess. Symlinks and other special files are left untracked.
+    ///
+    /// Returns the number of newly tracked entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inner path is `None`, or if the directory cannot be read or an entry cannot be opened.
+    pub fn rescan(&mut self) -> TempResult<usize> {
+        let root = self
+            .path
+            .clone()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let mut adopted = 0;
+        let mut pending = vec![root];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir).context(Operation::ReadDir, &dir)? {
+                let path = entry.context(Operation::ReadDir, &dir)?.path();
+                if path.file_name().and_then(OsStr::to_str).is_none() {
+                    continue;
+                }
+                let metadata = fs::symlink_metadata(&path).context(Operation::ReadDir, &path)?;
+                if metadata.is_dir() {
+                    if !self.tracks(&path) {
+                        self.dir_for(&path)?;
+                        adopted += 1;
+                    }
+                    pending.push(path);
+                } else if metadata.is_file() && !self.tracks(&path) {
+                    self.adopt(&path)?;
+                    adopted += 1;
+                }
+            }
+        }
+        Ok(adopted)
+    }
+
+    /// Retrieves a reference to a tracked subdirectory by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subdirectory to retrieve.
+    pub fn get_dir<S: AsRef<str>>(&self, name: S) -> Option<&TempDir> {
+        let name = name.as_ref();
+        self.dirs
+            .iter()
+            .find(|d| d.path().and_then(Path::file_name) == Some(OsStr::new(name)))
+    }
+
+    /// Retrieves a mutable reference to a tracked subdirectory by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subdirectory to retrieve.
+    pub fn get_dir_mut<S: AsRef<str>>(&mut self, name: S) -> Option<&mut TempDir> {
+        let name = name.as_ref();
+        self.dirs
+            .iter_mut()
+            .find(|d| d.path().and_then(Path::file_name) == Some(OsStr::new(name)))
+    }
+
+    /// Starts tracking a file created through the directory, subjecting it to the directory's quota.
+    fn track(&mut self, mut file: TempFile) -> &mut TempFile {
+        if let Some(ref quota) = self.quota {
+            file.attach_dir_quota(Arc::clone(quota));
+        }
+        self.files.push(file);
+        self.files.last_mut().unwrap()
+    }
+
+    /// Limits the total size of the files created through the directory and its subdirectories to `limit` bytes, or removes
+    /// the limit if `None`.
+    ///
+    /// Files already tracked, including those in existing subdirectories, count towards the limit. Writes through a tracked
+    /// `TempFile` and `set_len` which would exceed the limit fail with `TempError::QuotaExceeded`. The quota of a subdirectory
+    /// applies in addition to those of its parents and does not change them.
+    pub fn set_quota(&mut self, limit: Option<u64>) {
+        match (&self.quota, limit) {
+            (Some(quota), Some(limit)) => quota.set_limit(limit),
+            (Some(quota), None) => quota.set_limit(u64::MAX),
+            (None, Some(limit)) => self.bind_quota(limit, None),
+            (None, None) => {}
+        }
+    }
+
+    /// Gives the directory a new quota with the given limit, chained to `parent`, and moves the charges of every tracked file
+    /// in the tree over to it.
+    fn bind_quota(&mut self, limit: u64, parent: Option<Arc<DirQuota>>) {
+        let quota = DirQuota::new(limit, parent);
+        for file in &mut self.files {
+            file.attach_dir_quota(Arc::clone(&quota));
+        }
+        for dir in &mut self.dirs {
+            let limit = dir.quota.as_ref().map_or(u64::MAX, |q| q.limit());
+            dir.bind_quota(limit, Some(Arc::clone(&quota)));
+        }
+        self.quota = Some(quota);
+    }
+
+    /// Returns the total size limit of the files created through the directory in bytes, if one is set.
+    #[must_use]
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+            .as_ref()
+            .map(|q| q.limit())
+            .filter(|limit| *limit != u64::MAX)
+    }
+
+    /// Returns the total size in bytes charged against the directory's quota, or zero if no quota has been set.
+    #[must_use]
+    pub fn quota_used(&self) -> u64 {
+        self.quota.as_ref().map_or(0, |q| q.used())
     }
 
     /// Removes a file from the directory's management.
@@ -244,32 +722,68 @@ impl TempDir {
     ///
     /// # Arguments
     ///
-    /// * `filename` - The name of the file to retrieve.
+    /// * `filename` - The name of the file to retrieve, relative to the directory. Names which leave the directory never match.
     pub fn get_file<S: AsRef<str>>(&self, filename: S) -> Option<&TempFile> {
-        let filename = filename.as_ref();
-        self.files.iter().find(|f| {
-            f.path
-                .as_ref()
-                .and_then(|p| p.file_name())
-                .and_then(|n| n.to_str())
-                == Some(filename)
-        })
+        let path = join_within_lexically(self.path.as_ref()?, Path::new(filename.as_ref())).ok()?;
+        self.files
+            .iter()
+            .find(|f| f.path.as_deref() == Some(path.as_path()))
     }
 
     /// Retrieves a mutable reference to a temporary file by filename.
     ///
     /// # Arguments
     ///
-    /// * `filename` - The name of the file to retrieve.
+    /// * `filename` - The name of the file to retrieve, relative to the directory. Names which leave the directory never match.
     pub fn get_file_mut<S: AsRef<str>>(&mut self, filename: S) -> Option<&mut TempFile> {
-        let filename = filename.as_ref();
-        self.files.iter_mut().find(|f| {
-            f.path
-                .as_ref()
-                .and_then(|p| p.file_name())
-                .and_then(|n| n.to_str())
-                == Some(filename)
-        })
+        let path = join_within_lexically(self.path.as_ref()?, Path::new(filename.as_ref())).ok()?;
+        self.files
+            .iter_mut()
+            .find(|f| f.path.as_deref() == Some(path.as_path()))
+    }
+
+    /// Creates a tracked lock file with the given name in the directory, if it does not exist yet, and takes an exclusive
+    /// advisory lock on it, blocking until the lock is available.
+    ///
+    /// The lock is held through a separate handle for the lifetime of the returned guard, so processes sharing the directory
+    /// can coordinate through the same name. A lock file created by this call is removed along with the directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the lock file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::EscapesDirectory)` if `name` leaves the directory, or an error if the inner path is `None`, or if
+    /// the lock file cannot be created, opened or locked.
+    #[cfg(unix)]
+    pub fn lockfile<S: AsRef<str>>(&mut self, name: S) -> TempResult<LockFile> {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::fs::OpenOptionsExt;
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        let lock_path = join_within(dir, Path::new(name.as_ref()))?;
+        if self.get_file(name.as_ref()).is_none() {
+            match TempFile::new(&lock_path) {
+                Ok(file) => {
+                    self.track(file);
+                }
+                Err(TempError::PathExists(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(TempConfig::current().file_mode)
+            .open(&lock_path)
+            .context(Operation::Open, &lock_path)?;
+        crate::lock::lock_fd(file.as_raw_fd(), true, true).context(Operation::Lock, &lock_path)?;
+        Ok(LockFile::new(lock_path, file))
     }
 
     /// Returns the path of the temporary directory.
@@ -278,10 +792,103 @@ impl TempDir {
         self.path.as_deref()
     }
 
+    /// Writes an owner marker into the directory, so that a `Reaper` can remove it if this process dies without cleaning up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inner path is `None` or if the marker cannot be written.
+    pub fn mark_owner(&self) -> TempResult<()> {
+        let dir = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Temporary directory path is not set"))?;
+        crate::reaper::write_owner_marker(dir)?;
+        Ok(())
+    }
+
     /// Consumes the `TempDir`, returning its path and preventing cleanup.
+    ///
+    /// Every tracked file and subdirectory is disarmed as well, so the directory is kept with all of its contents. Equivalent to [`keep`](TempDir::keep).
     #[must_use]
-    pub fn into_path(mut self) -> Option<PathBuf> {
-        self.path.take()
+    pub fn into_path(self) -> Option<PathBuf> {
+        self.keep()
+    }
+
+    /// Consumes the `TempDir`, keeping the directory and, recursively, all of its tracked files and subdirectories on disk.
+    ///
+    /// Returns the path of the directory, or `None` if the inner path is `None`.
+    #[must_use]
+    pub fn keep(mut self) -> Option<PathBuf> {
+        self.disarm_for_cleanup().map(|(path, _)| path)
+    }
+
+    /// Keeps the directory and its contents instead of removing them if it is dropped while the thread is panicking, such as
+    /// in a failing test. The path of the kept directory is printed to stderr.
+    pub fn keep_on_panic(&mut self) -> &mut Self {
+        self.keep.keep_on_panic = true;
+        self
+    }
+
+    /// Keeps the directory and its contents instead of removing them if it is dropped while the environment variable
+    /// `TEMPFS_KEEP` is set to `1`. The path of the kept directory is printed to stderr.
+    pub fn keep_if_env(&mut self) -> &mut Self {
+        self.keep.keep_if_env = true;
+        self
+    }
+
+    /// Returns the policy deciding when the directory is kept on drop, for builder settings which override the configuration.
+    pub(crate) fn cleanup_policy_mut(&mut self) -> &mut CleanupPolicy {
+        &mut self.keep
+    }
+
+    /// Checks if the directory has to be kept rather than removed on drop.
+    fn should_keep(&self) -> bool {
+        (self.keep.keep_on_panic && thread::panicking())
+            || (self.keep.keep_if_env && env::var_os(KEEP_ENV_VAR).is_some_and(|v| v == "1"))
+    }
+
+    /// Removes the temporary directory and all of its contents, reporting what could not be removed instead of ignoring it like `Drop`.
+    ///
+    /// Removal continues past entries which fail; each of them is listed in the returned report along with the reason.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory itself cannot be listed.
+    pub fn cleanup(mut self) -> TempResult<CleanupReport> {
+        let mut report = CleanupReport::default();
+        if let Some((path, created)) = self.disarm_for_cleanup() {
+            remove_dir_and_parents(&path, created.as_deref(), &mut report)
+                .context(Operation::Remove, &path)?;
+        }
+        Ok(report)
+    }
+
+    /// Disarms the directory and its tracked files and subdirectories, returning its path and the first parent directory created for it.
+    ///
+    /// Removing the path recursively removes the contents deepest-first, before the directory itself.
+    pub(crate) fn disarm_for_cleanup(&mut self) -> Option<(PathBuf, Option<PathBuf>)> {
+        for dir in &mut self.dirs {
+            dir.disarm_for_cleanup();
+        }
+        self.dirs.clear();
+        for file in &mut self.files {
+            file.forget_path();
+        }
+        self.files.clear();
+        let created = self.created_parent.take();
+        self.forget_path().map(|p| (p, created))
+    }
+
+    /// Lists the files and subdirectories managed by the directory and, recursively, by its subdirectories.
+    ///
+    /// Returns `None` if the inner path is `None`.
+    #[must_use]
+    pub fn list_tree(&self) -> Option<DirTree<'_>> {
+        Some(DirTree {
+            path: self.path.as_deref()?,
+            files: self.list_files(),
+            dirs: self.dirs.iter().filter_map(TempDir::list_tree).collect(),
+        })
     }
 
     /// Lists the paths of all files managed by the directory.
@@ -293,16 +900,15 @@ impl TempDir {
             .collect()
     }
 
-    #[cfg(feature = "rand_gen")]
     /// Creates a new temporary directory with a random name within the given parent directory.
     ///
     /// # Arguments
     ///
-    /// * `path` - The parent directory in which to create the temporary directory. If a relative path is provided, it is resolved relative to the system temporary directory.
+    /// * `path` - The parent directory in which to create the temporary directory. If a relative path is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
     ///
     /// # Errors
     ///
-    /// Returns an error if a unique directory name cannot be generated or if directory creation fails.
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if directory creation fails.
     pub fn new_in<P: AsRef<Path>>(path: P) -> TempResult<Self> {
         Self::new_random(Some(path))
     }
@@ -364,16 +970,19 @@ impl TempDir {
 
 impl Drop for TempDir {
     fn drop(&mut self) {
-        match (self.path.take(), self.created_parent.take()) {
-            (Some(p), None) => {
-                self.files.clear();
-                let _ = fs::remove_dir_all(p);
+        let keep = self.should_keep();
+        if let Some((path, created)) = self.disarm_for_cleanup() {
+            if keep {
+                eprintln!("tempfs: keeping temporary directory {}", path.display());
+                return;
+            }
+            let mut report = CleanupReport::default();
+            if let Err(e) = remove_dir_and_parents(&path, created.as_deref(), &mut report) {
+                report_drop_failure(&path, &e);
             }
-            (Some(_), Some(d)) => {
-                self.files.clear();
-                let _ = fs::remove_dir_all(d);
+            for failure in report.failures() {
+                report_drop_failure(&failure.path, &failure.error);
             }
-            _ => {}
         }
     }
 }
@@ -1,14 +1,9 @@
-#[cfg(feature = "rand_gen")]
-use crate::global_consts::{num_retry, rand_fn_len, valid_chars};
+#[cfg(feature = "mmap_support")]
+use crate::mapped_temp_file::{MapMode, MappedTempFile};
 #[cfg(feature = "mmap_support")]
 use memmap2::{Mmap, MmapMut, MmapOptions};
-#[cfg(feature = "rand_gen")]
-use rand::Rng;
 #[cfg(feature = "display_files")]
-use std::fmt::Display;
-use std::fmt::{Debug, Formatter};
-#[cfg(unix)]
-use std::fs::Permissions;
+use std::fmt::{Display, Formatter};
 use std::fs::{File, OpenOptions};
 use std::io::{self, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
 use std::ops::{Deref, DerefMut};
@@ -17,8 +12,92 @@ use std::os::fd::{AsRawFd, RawFd};
 use std::path::{Path, PathBuf};
 use std::{env, fs};
 
-use crate::error::{TempError, TempResult};
-use crate::helpers::normalize_path;
+use crate::cleanup::{remove_empty_parents, remove_file_and_parents};
+use crate::config::TempConfig;
+use crate::error::{IoResultExt, Operation, PersistStep, TempError, TempResult};
+use crate::helpers::{normalize_path, MoveKind};
+use crate::location::TempLocation;
+#[cfg(unix)]
+use crate::lock::TempFileLock;
+use crate::name_gen::NameGenerator;
+use crate::quota::{DirQuota, FileQuota};
+
+/// Options used when creating the file behind a `TempFile`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FileSpec {
+    /// The permission mode the file is given on Unix.
+    pub(crate) mode: u32,
+    /// Whether writes always append to the end of the file.
+    pub(crate) append: bool,
+    /// Whether the returned handle is opened read-only.
+    pub(crate) read_only: bool,
+}
+
+impl Default for FileSpec {
+    /// Returns the options of a plain read-write file with the permission mode of the current `TempConfig`.
+    fn default() -> Self {
+        Self {
+            mode: TempConfig::current().file_mode,
+            append: false,
+            read_only: false,
+        }
+    }
+}
+
+/// How a `TempFile` being renamed or persisted treats an entry already present at the target path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PersistMode {
+    /// Fail with `TempError::PathExists` if the target already exists, leaving both files untouched.
+    ///
+    /// On Linux this uses `renameat2(RENAME_NOREPLACE)`, on other Unix systems `link`+`unlink`.
+    NoClobber,
+    /// Atomically replace any existing file at the target.
+    #[default]
+    Overwrite,
+}
+
+/// A set of seals restricting how a memfd-backed `TempFile` may be modified, see [`TempFile::seal`].
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SealFlags(libc::c_int);
+
+#[cfg(target_os = "linux")]
+impl SealFlags {
+    /// No seals.
+    pub const NONE: Self = Self(0);
+    /// Prevents any further seals from being added (`F_SEAL_SEAL`).
+    pub const SEAL: Self = Self(libc::F_SEAL_SEAL);
+    /// Prevents the file from shrinking (`F_SEAL_SHRINK`).
+    pub const SHRINK: Self = Self(libc::F_SEAL_SHRINK);
+    /// Prevents the file from growing (`F_SEAL_GROW`).
+    pub const GROW: Self = Self(libc::F_SEAL_GROW);
+    /// Prevents the contents of the file from being modified (`F_SEAL_WRITE`).
+    pub const WRITE: Self = Self(libc::F_SEAL_WRITE);
+    /// Makes the file immutable: no writes, no size changes and no further seals.
+    pub const IMMUTABLE: Self =
+        Self(libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE);
+
+    /// Checks if every seal in `other` is also in `self`.
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::BitOr for SealFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::BitOrAssign for SealFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
 
 /// A temporary file that is automatically deleted when dropped unless explicitly closed.
 ///
@@ -33,16 +112,24 @@ pub struct TempFile {
     file: Option<File>,
     /// Directories created to hold the temporary file that did not exist.
     created_parent: Option<PathBuf>,
+    /// The directory an anonymous file was opened in, while it has not yet been given a name.
+    anonymous_dir: Option<PathBuf>,
+    /// Whether the file is a memfd, which lives only in memory and never has a path.
+    memfd: bool,
+    /// The byte limits the file is subject to.
+    quota: FileQuota,
+    /// The entry recording the file in the process-wide registry, if it is enabled.
+    registry_id: Option<usize>,
 }
 
 impl TempFile {
     /// Creates a new temporary file at the specified path.
     ///
-    /// The file is created with read and write permissions.
+    /// The file is opened for reading and writing, and created with the file mode of the current `TempConfig` (0o600 by default) on Unix.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path at which to create the file. If a relative path is provided, it is resolved relative to the system temporary directory.
+    /// * `path` - The path at which to create the file. If a relative path is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
     ///
     /// # Errors
     ///
@@ -52,19 +139,36 @@ impl TempFile {
         let path_buf = if path_ref.is_absolute() {
             path_ref
         } else {
-            env::temp_dir().join(path_ref)
+            crate::config::base_dir()?.join(path_ref)
         };
-        let (created, file) = Self::open(&path_buf)?;
-        Ok(Self {
-            path: Some(path_buf),
-            file: Some(file),
-            created_parent: created,
-        })
+        Self::create(path_buf, &FileSpec::default())
+    }
+
+    /// Creates a new temporary file at the specified path within a storage location.
+    ///
+    /// The file is opened for reading and writing, and created with the file mode of the current `TempConfig` (0o600 by default) on Unix.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - Where to place the file if `path` is relative.
+    /// * `path` - The path at which to create the file. If a relative path is provided, it is resolved relative to the directory `location` resolves to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the location cannot be resolved or if the file cannot be created.
+    pub fn new_at<P: AsRef<Path>>(location: &TempLocation, path: P) -> TempResult<TempFile> {
+        let path_ref = normalize_path(path.as_ref());
+        let path_buf = if path_ref.is_absolute() {
+            path_ref
+        } else {
+            location.resolve()?.join(path_ref)
+        };
+        Self::create(path_buf, &FileSpec::default())
     }
 
     /// Creates a new temporary file at the specified path.
     ///
-    /// The file is created with read and write permissions.
+    /// The file is opened for reading and writing, and created with the file mode of the current `TempConfig` (0o600 by default) on Unix.
     ///
     /// # Arguments
     ///
@@ -80,12 +184,36 @@ impl TempFile {
         } else {
             env::current_dir()?.join(path_ref)
         };
-        let (created, file) = Self::open(&path_buf)?;
-        Ok(Self {
-            path: Some(path_buf),
-            file: Some(file),
-            created_parent: created,
-        })
+        Self::create(path_buf, &FileSpec::default())
+    }
+
+    /// Creates a new temporary file at the specified path with the given permission mode.
+    ///
+    /// The mode is applied as the file is created and is subject to the umask, for example 0o755 for a temporary script or 0o640 for group access.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path at which to create the file. If a relative path is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
+    /// * `mode` - The permission mode of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created.
+    #[cfg(unix)]
+    pub fn new_with_mode<P: AsRef<Path>>(path: P, mode: u32) -> TempResult<TempFile> {
+        let path_ref = normalize_path(path.as_ref());
+        let path_buf = if path_ref.is_absolute() {
+            path_ref
+        } else {
+            crate::config::base_dir()?.join(path_ref)
+        };
+        Self::create(
+            path_buf,
+            &FileSpec {
+                mode,
+                ..FileSpec::default()
+            },
+        )
     }
 
     /// Converts the `TempFile` into a permanent file.
@@ -94,12 +222,14 @@ impl TempFile {
     ///
     /// Returns an error if the inner file is `None`.
     pub fn persist(&mut self) -> TempResult<File> {
-        self.path = None;
+        self.forget_path();
         self.file.take().ok_or(TempError::FileIsNone)
     }
 
     /// Renames the temporary file and then persists it.
     ///
+    /// The rename is atomic; any existing file at the new name is replaced. See [`rename`](TempFile::rename).
+    ///
     /// # Errors
     ///
     /// Returns an error if renaming or persisting the file fails.
@@ -108,6 +238,79 @@ impl TempFile {
         self.persist()
     }
 
+    /// Atomically moves the temporary file to `path` and persists it.
+    ///
+    /// Readers of `path` never observe a partially written file. See [`rename_with`](TempFile::rename_with) for how `path` is resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to persist the file at.
+    /// * `mode` - Whether an existing file at `path` may be replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::PathExists)` if `mode` is `NoClobber` and `path` exists, or an error if moving or persisting the file fails.
+    pub fn persist_to<P: AsRef<Path>>(&mut self, path: P, mode: PersistMode) -> TempResult<File> {
+        self.rename_with(path, mode)?;
+        self.persist()
+    }
+
+    /// Durably moves the temporary file to `path` and persists it, so that it survives power loss.
+    ///
+    /// The file's contents are synced, the file is atomically renamed (see [`rename_with`](TempFile::rename_with)),
+    /// and then the new and old parent directories are synced so the rename itself reaches the storage device.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to persist the file at.
+    /// * `mode` - Whether an existing file at `path` may be replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::Persist)` naming the step which failed, `Err(TempError::PathExists)` if `mode` is `NoClobber` and `path` exists,
+    /// or `Err(TempError::FileIsNone)` if the file handle is not available. A failed copy, reopen or removal while moving the
+    /// file to another filesystem is reported as `Err(TempError::Failed)` with its own operation and path.
+    pub fn persist_durable<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        mode: PersistMode,
+    ) -> TempResult<File> {
+        let old_dir = self
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf);
+        let path = path.as_ref();
+        self.file()?
+            .sync_all()
+            .persist_context(PersistStep::SyncFile, &self.context_path())?;
+        self.rename_with(path, mode).map_err(|e| match e {
+            TempError::Failed {
+                operation: Operation::Rename,
+                path,
+                source,
+            } => TempError::Persist {
+                step: PersistStep::Rename,
+                path,
+                source,
+            },
+            e => e,
+        })?;
+        let new_dir = self
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf);
+        let mut dirs = vec![new_dir];
+        if old_dir != dirs[0] {
+            dirs.push(old_dir);
+        }
+        for dir in dirs.into_iter().flatten() {
+            crate::helpers::sync_dir(&dir).persist_context(PersistStep::SyncDir, &dir)?;
+        }
+        self.persist()
+    }
+
     /// Renames the temporary file (in the current directory) and persists it.
     ///
     /// # Errors
@@ -118,58 +321,44 @@ impl TempFile {
         self.persist()
     }
 
-    #[cfg(feature = "rand_gen")]
     /// Creates a new temporary file with a random name in the given directory.
     ///
-    /// The file name is generated using random ASCII characters.
+    /// The name comes from the `NameGenerator` of the current `TempConfig`, random alphanumeric characters by default.
     ///
     /// # Arguments
     ///
-    /// * `dir` - The directory in which to create the file. If `None`, the system temporary directory is used. If a relative directory is provided, it is resolved relative to the system temporary directory.
+    /// * `dir` - The directory in which to create the file. If `None`, the base directory of the current `TempConfig` is used. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
     ///
     /// # Errors
     ///
-    /// Returns an error if a unique filename cannot be generated or if file creation fails.
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if file creation fails.
     pub fn new_random<P: AsRef<Path>>(dir: Option<P>) -> TempResult<Self> {
         let dir_buf = if let Some(d) = dir {
             let path_ref = normalize_path(d.as_ref());
             if path_ref.is_absolute() {
                 path_ref
             } else {
-                env::temp_dir().join(path_ref)
+                crate::config::base_dir()?.join(path_ref)
             }
         } else {
-            env::temp_dir()
+            crate::config::base_dir()?
         };
-        let mut rng = rand::rng();
-        for _ in 0..num_retry() {
-            let name: String = (0..rand_fn_len())
-                .map(|_| {
-                    let idx = rng.random_range(0..valid_chars().len());
-                    valid_chars()[idx] as char
-                })
-                .collect();
-            let full_path = dir_buf.join(&name);
-            if !full_path.exists() {
-                let (created, file) = Self::open(&full_path)?;
-                return Ok(Self {
-                    path: Some(full_path),
-                    file: Some(file),
-                    created_parent: created,
-                });
-            }
-        }
-        Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            "Could not generate a unique filename",
+        let config = TempConfig::current();
+        let spec = FileSpec::default();
+        crate::builder::create_unique(
+            &dir_buf,
+            "",
+            "",
+            config.name_len,
+            config.retries,
+            &*config.names,
+            |path| Self::create(path, &spec),
         )
-        .into())
     }
 
-    #[cfg(feature = "rand_gen")]
     /// Creates a new temporary file with a random name in the given directory.
     ///
-    /// The file name is generated using random ASCII characters.
+    /// The name comes from the `NameGenerator` of the current `TempConfig`, random alphanumeric characters by default.
     ///
     /// # Arguments
     ///
@@ -177,7 +366,7 @@ impl TempFile {
     ///
     /// # Errors
     ///
-    /// Returns an error if a unique filename cannot be generated or if file creation fails.
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if file creation fails.
     pub fn new_random_here<P: AsRef<Path>>(dir: Option<P>) -> TempResult<Self> {
         if let Some(dir) = dir {
             let d_ref = normalize_path(dir.as_ref());
@@ -191,34 +380,141 @@ impl TempFile {
         }
     }
 
+    /// Creates a new temporary file in the given directory, named by a `NameGenerator`.
+    ///
+    /// A name which is already taken is skipped by generating another one.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory in which to create the file. If `None`, the base directory of the current `TempConfig` is used. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
+    /// * `names` - The generator of the file name, such as `SeededNames` for reproducible names.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::NameSpaceExhausted)` if no unused name is found within the retries of the current `TempConfig`, or an error if file creation fails.
+    pub fn new_random_with<P: AsRef<Path>>(
+        dir: Option<P>,
+        names: &dyn NameGenerator,
+    ) -> TempResult<Self> {
+        let dir_buf = match dir {
+            Some(d) => crate::config::base_dir()?.join(normalize_path(d.as_ref())),
+            None => crate::config::base_dir()?,
+        };
+        let config = TempConfig::current();
+        crate::builder::create_unique(
+            &dir_buf,
+            "",
+            "",
+            config.name_len,
+            config.retries,
+            names,
+            |path| Self::create(path, &FileSpec::default()),
+        )
+    }
+
+    /// Creates a new temporary file at an already resolved path using the given options.
+    pub(crate) fn create(path: PathBuf, spec: &FileSpec) -> TempResult<Self> {
+        let (created, file) = Self::open_with(&path, spec)?;
+        Ok(Self {
+            path: Some(path),
+            file: Some(file),
+            created_parent: created,
+            anonymous_dir: None,
+            memfd: false,
+            quota: FileQuota::default(),
+            registry_id: None,
+        }
+        .registered())
+    }
+
+    /// Records the file in the process-wide registry, if it is enabled.
+    fn registered(mut self) -> Self {
+        if let Some(ref path) = self.path {
+            self.registry_id =
+                crate::registry::register(path, false, self.created_parent.as_deref());
+        }
+        self
+    }
+
+    /// Returns the path to report in errors about the file: its path, the directory of an anonymous file, or the fd path of a memfd.
+    fn context_path(&self) -> PathBuf {
+        let path = self.path.clone().or_else(|| self.anonymous_dir.clone());
+        #[cfg(target_os = "linux")]
+        let path = path.or_else(|| self.fd_path());
+        path.unwrap_or_default()
+    }
+
+    /// Returns `TempError::InvalidFileOrPath` for the file, for operations it does not support.
+    fn invalid(&self) -> TempError {
+        TempError::InvalidFileOrPath(self.path.clone().unwrap_or_default())
+    }
+
+    /// Clears the path, disarming deletion, and removes the file from the process-wide registry.
+    pub(crate) fn forget_path(&mut self) -> Option<PathBuf> {
+        if let Some(id) = self.registry_id.take() {
+            crate::registry::unregister(id);
+        }
+        self.path.take()
+    }
+
+    /// Sets the path after the file was renamed or linked, keeping the process-wide registry up to date.
+    fn set_path(&mut self, path: PathBuf) {
+        match self.registry_id {
+            Some(id) => crate::registry::update(id, &path),
+            None => {
+                self.registry_id =
+                    crate::registry::register(&path, false, self.created_parent.as_deref());
+            }
+        }
+        self.path = Some(path);
+    }
+
     /// Opens a new file at the specified path, creating any missing parent directories if necessary.
     ///
     /// If the file already exists, an error is returned. On success, this function returns a tuple containing:
     /// - An `Option<PathBuf>` representing the created directory (if any),
     /// - The newly created file handle.
-    fn open(path: &Path) -> TempResult<(Option<PathBuf>, File)> {
-        #[cfg(unix)]
-        use std::os::unix::fs::PermissionsExt;
+    ///
+    /// The mode is applied as the file is created, so it is subject to the umask. Missing parent directories are created with the
+    /// directory mode of the current `TempConfig`.
+    fn open_with(path: &Path, spec: &FileSpec) -> TempResult<(Option<PathBuf>, File)> {
         let mut created = None;
         let par = path.parent();
-        if path.exists() {
-            return Err(TempError::PathExists(path.to_path_buf()));
-        } else if let Some(c) = crate::helpers::first_missing_directory_component(path) {
-            fs::create_dir_all(par.unwrap())?;
+        if let Some(c) = crate::helpers::first_missing_directory_component(path) {
+            let par = par.unwrap();
+            crate::helpers::dir_builder(TempConfig::current().dir_mode)
+                .recursive(true)
+                .create(par)
+                .context(Operation::CreateParent, par)?;
             created = Some(c);
         }
-        let file = OpenOptions::new()
+        let mut options = OpenOptions::new();
+        options
             .create_new(true)
             .read(true)
             .write(true)
-            .open(path)
-            .map_err(Into::into);
-        if file.is_err() && created.is_some() {
-            fs::remove_dir_all(created.clone().unwrap())?;
-        }
+            .append(spec.append);
         #[cfg(unix)]
-        fs::set_permissions(path, Permissions::from_mode(0o700))?;
-        file.map(|file| (created, file))
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(spec.mode);
+        }
+        let file = match options.open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if let Some(ref c) = created {
+                    let _ = remove_empty_parents(path, c);
+                }
+                if e.kind() == io::ErrorKind::AlreadyExists {
+                    return Err(TempError::PathExists(path.to_path_buf()));
+                }
+                return Err(TempError::failed(Operation::CreateFile, path, e));
+            }
+        };
+        if spec.read_only {
+            return Ok((created, File::open(path).context(Operation::Open, path)?));
+        }
+        Ok((created, file))
     }
 
     /// Returns a mutable reference to the file handle.
@@ -245,63 +541,104 @@ impl TempFile {
         self.path.as_deref()
     }
 
-    /// Copies the temporary file to a new path and deletes the original file, "renaming" it.
+    /// Atomically renames the temporary file, replacing any existing file at the new path.
     ///
     /// # Arguments
     ///
-    /// * `new_path` - The new path for the file. If `new_path` is relative, it is appended to the old file's parent directory.
+    /// * `new_path` - The new path for the file. If `new_path` is a bare file name, it is placed in the old file's parent directory.
     ///
     /// # Errors
     ///
-    /// Returns an error if the file copy or deletion operation fails, or if the old file's parent directory cannot be determined when `new_path` is relative.
+    /// Returns an error if moving the file fails, or if the old file's parent directory cannot be determined when `new_path` is a bare file name.
     pub fn rename<P: AsRef<Path>>(&mut self, new_path: P) -> TempResult<()> {
-        let mut new_path = normalize_path(new_path.as_ref());
-        let pat = new_path.to_str().unwrap_or("");
-        let mut mod_path = false;
-        if !pat.contains('/') && !pat.contains('\\') {
-            mod_path = true;
-        }
-        if let Some(ref old_path) = self.path {
-            if mod_path {
-                new_path = old_path
-                    .parent()
-                    .ok_or(TempError::IO(io::Error::new(
-                        io::ErrorKind::NotFound,
-                        "Old path parent not found",
-                    )))?
-                    .join(new_path);
-            }
-            fs::copy(old_path, new_path.clone())?;
-            fs::remove_file(old_path)?;
-            self.path = Some(new_path);
-        }
-        Ok(())
+        self.rename_with(new_path, PersistMode::Overwrite)
+    }
+
+    /// Atomically renames the temporary file, treating an existing file at the new path according to `mode`.
+    ///
+    /// When the new path is on the same filesystem, this is a single `rename(2)` and the open handle keeps referring to the file.
+    /// Across filesystems the contents are copied to a hidden sibling of the new path, synced, and renamed into place; the handle is then reopened at the new path.
+    ///
+    /// An anonymous or memfd-backed file, which has no path yet, is materialized at the new path as with
+    /// [`link_into`](TempFile::link_into).
+    ///
+    /// # Arguments
+    ///
+    /// * `new_path` - The new path for the file. If `new_path` is a bare file name, it is placed in the old file's parent directory,
+    ///   or the directory an anonymous file was created in.
+    /// * `mode` - Whether an existing file at `new_path` may be replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::PathExists)` if `mode` is `NoClobber` and `new_path` exists, `Err(TempError::FileIsNone)` if the
+    /// file was already persisted or closed, or an error if moving the file fails.
+    pub fn rename_with<P: AsRef<Path>>(
+        &mut self,
+        new_path: P,
+        mode: PersistMode,
+    ) -> TempResult<()> {
+        let new_path = normalize_path(new_path.as_ref());
+        let Some(ref old_path) = self.path else {
+            #[cfg(unix)]
+            return self.rename_unnamed(new_path, mode);
+            #[cfg(not(unix))]
+            return Err(match self.file {
+                Some(_) => TempError::InvalidFileOrPath(new_path),
+                None => TempError::FileIsNone,
+            });
+        };
+        let new_path = if Self::is_bare_name(&new_path) {
+            old_path
+                .parent()
+                .ok_or_else(|| TempError::InvalidFileOrPath(old_path.clone()))?
+                .join(new_path)
+        } else {
+            new_path
+        };
+        self.move_to(new_path, mode)
     }
 
-    /// Copies the temporary file to a new path in the current directory and deletes the original file, "renaming" it.
+    /// Atomically renames the temporary file into the current directory, replacing any existing file at the new path.
     ///
     /// # Arguments
     ///
-    /// * `new_path` - The new path for the file. If `new_path` is relative, it is resolved relative to the current working directory.
+    /// * `new_path` - The new path for the file. If `new_path` is a bare file name, it is resolved relative to the current working directory.
     ///
     /// # Errors
     ///
-    /// Returns an error if the file copy or deletion operation fails.
+    /// Returns an error if moving the file fails.
     pub fn rename_here<P: AsRef<Path>>(&mut self, new_path: P) -> TempResult<()> {
         let mut new_path = normalize_path(new_path.as_ref());
-        let pat = new_path.to_str().unwrap_or("");
-        let mut mod_path = false;
-        if !pat.contains('/') && !pat.contains('\\') {
-            mod_path = true;
-        }
-        if let Some(ref old_path) = self.path {
-            if mod_path {
-                new_path = env::current_dir()?.join(new_path);
+        if Self::is_bare_name(&new_path) {
+            new_path = env::current_dir()?.join(new_path);
+        }
+        self.rename_with(new_path, PersistMode::Overwrite)
+    }
+
+    /// Whether a path consists of only a file name, without any directory separators.
+    pub(crate) fn is_bare_name(path: &Path) -> bool {
+        let pat = path.to_str().unwrap_or("");
+        !pat.contains('/') && !pat.contains('\\')
+    }
+
+    /// Moves the file to `new_path`, reopening the handle if the move had to copy the contents to another filesystem.
+    fn move_to(&mut self, new_path: PathBuf, mode: PersistMode) -> TempResult<()> {
+        let Some(ref old_path) = self.path else {
+            return Ok(());
+        };
+        if crate::helpers::move_file(old_path, &new_path, mode)? == MoveKind::Copied {
+            if let Some(ref mut old_file) = self.file {
+                let pos = old_file.stream_position()?;
+                let mut file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&new_path)
+                    .context(Operation::Open, &new_path)?;
+                file.seek(SeekFrom::Start(pos))?;
+                self.file = Some(file);
             }
-            fs::copy(old_path, new_path.clone())?;
-            fs::remove_file(old_path)?;
-            self.path = Some(new_path);
         }
+        self.set_path(new_path);
         Ok(())
     }
 
@@ -313,7 +650,9 @@ impl TempFile {
     ///
     /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or if syncing fails.
     pub fn sync_all(&self) -> TempResult<()> {
-        self.file()?.sync_all().map_err(Into::into)
+        self.file()?
+            .sync_all()
+            .context(Operation::Sync, &self.context_path())
     }
 
     /// Flushes the file and disarms automatic deletion.
@@ -324,8 +663,9 @@ impl TempFile {
     ///
     /// Returns an error if flushing fails or if the file handle is not available.
     pub fn disarm(mut self) -> TempResult<()> {
-        self.file_mut()?.flush().map_err(Into::<TempError>::into)?;
-        self.path = None;
+        let path = self.context_path();
+        self.file_mut()?.flush().context(Operation::Flush, &path)?;
+        self.forget_path();
         Ok(())
     }
 
@@ -337,8 +677,9 @@ impl TempFile {
     ///
     /// Returns an error if flushing fails or if the file handle is not available.
     pub fn close(mut self) -> TempResult<()> {
-        self.file_mut()?.flush().map_err(Into::<TempError>::into)?;
-        self.path = None;
+        let path = self.context_path();
+        self.file_mut()?.flush().context(Operation::Flush, &path)?;
+        self.forget_path();
         self.file = None;
         Ok(())
     }
@@ -351,32 +692,148 @@ impl TempFile {
     ///
     /// Returns `Err(TempError::FileIsNone)` if the file handle has already been taken.
     pub fn into_inner(mut self) -> TempResult<File> {
-        self.path = None;
+        self.forget_path();
         self.file.take().ok_or(TempError::FileIsNone)
     }
 
     /// Checks if the file is still active.
+    ///
+    /// An anonymous file is active until it is closed or its handle is taken.
     #[must_use]
     pub fn is_active(&self) -> bool {
-        self.path.is_some()
+        self.path.is_some() || ((self.anonymous_dir.is_some() || self.memfd) && self.file.is_some())
     }
 
     /// Deletes the temporary file immediately.
     ///
-    /// This method flushes the file, removes it from the filesystem, and disarms automatic deletion.
+    /// This method flushes the file, removes it (and any parent directories created for it which are left empty) from the filesystem,
+    /// and disarms automatic deletion.
     ///
     /// # Errors
     ///
     /// Returns an error if flushing fails, if the file handle is not available, or if file removal fails.
     pub fn delete(mut self) -> TempResult<()> {
-        self.file_mut()?.flush().map_err(Into::<TempError>::into)?;
-        if let Some(ref path) = self.path {
-            fs::remove_file(path)?;
-            self.path = None;
+        let path = self.context_path();
+        self.file_mut()?.flush().context(Operation::Flush, &path)?;
+        self.remove_now()
+            .map_err(|(path, e)| TempError::failed(Operation::Remove, &path, e))
+    }
+
+    /// Closes and removes the temporary file, reporting any error instead of ignoring it like `Drop`.
+    ///
+    /// Parent directories created for the file are removed as well, but only if they are empty, since other code may have put files
+    /// into them. A file which no longer exists is not an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if removal fails.
+    pub fn cleanup(mut self) -> TempResult<()> {
+        self.file = None;
+        self.remove_now()
+            .map_err(|(path, e)| TempError::failed(Operation::Remove, &path, e))
+    }
+
+    /// Removes the file and any parent directories created for it which are left empty, and disarms deletion.
+    ///
+    /// On failure, returns the path which could not be removed along with the error. Entries which no longer exist are ignored.
+    fn remove_now(&mut self) -> Result<(), (PathBuf, io::Error)> {
+        let created = self.created_parent.take();
+        match self.forget_path() {
+            Some(p) => remove_file_and_parents(&p, created.as_deref()),
+            None => Ok(()),
+        }
+    }
+
+    /// Limits the size of the file to `limit` bytes, or removes the limit if `None`.
+    ///
+    /// Writes through the `TempFile` and `set_len` which would grow the file past the limit fail with `TempError::QuotaExceeded`
+    /// (carried inside an `io::Error` of kind `QuotaExceeded` from the `Write` impl). Writes through the inner `File` are not checked.
+    pub fn set_quota(&mut self, limit: Option<u64>) {
+        self.quota.set_limit(limit);
+    }
+
+    /// Returns the size limit of the file in bytes, if one is set.
+    #[must_use]
+    pub fn quota(&self) -> Option<u64> {
+        self.quota.limit()
+    }
+
+    /// Subjects the file to a directory's aggregate quota.
+    pub(crate) fn attach_dir_quota(&mut self, dir: std::sync::Arc<DirQuota>) {
+        let size = self
+            .file
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .map_or(0, |m| m.len());
+        self.quota.attach(dir, size);
+    }
+
+    /// Truncates or extends the file to `size` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, `Err(TempError::QuotaExceeded)` if
+    /// extending the file would exceed its quota, or an error if resizing fails.
+    pub fn set_len(&self, size: u64) -> TempResult<()> {
+        let file = self.file()?;
+        if self.quota.is_active() {
+            self.quota.reserve(size)?;
+        }
+        if let Err(e) = file.set_len(size) {
+            self.settle_quota();
+            return Err(TempError::failed(
+                Operation::Resize,
+                &self.context_path(),
+                e,
+            ));
         }
+        self.quota.settle(size);
         Ok(())
     }
 
+    /// Records the actual size of the file with its quota, returning space reserved for a failed or short write.
+    fn settle_quota(&self) {
+        if !self.quota.is_active() {
+            return;
+        }
+        if let Some(meta) = self.file.as_ref().and_then(|f| f.metadata().ok()) {
+            self.quota.settle(meta.len());
+        }
+    }
+
+    /// Checks that writing `len` bytes at the current position keeps the file within its quota.
+    fn check_quota(&mut self, len: usize) -> io::Result<()> {
+        if !self.quota.is_active() {
+            return Ok(());
+        }
+        let Some(ref mut file) = self.file else {
+            return Ok(());
+        };
+        let size = file.metadata()?.len();
+        let pos = if Self::is_append(file) {
+            size
+        } else {
+            file.stream_position()?
+        };
+        let end = pos.saturating_add(len as u64);
+        self.quota.reserve(end.max(size)).map_err(io::Error::from)
+    }
+
+    /// Checks if writes to `file` always go to its end.
+    fn is_append(file: &File) -> bool {
+        #[cfg(unix)]
+        {
+            // SAFETY: `F_GETFL` takes no argument and has no memory safety requirements.
+            let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+            flags >= 0 && flags & libc::O_APPEND != 0
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = file;
+            false
+        }
+    }
+
     /// Retrieves metadata of the file.
     ///
     /// # Errors
@@ -384,7 +841,11 @@ impl TempFile {
     /// Returns an error if the metadata cannot be accessed or if the file has been closed.
     pub fn metadata(&self) -> TempResult<fs::Metadata> {
         if let Some(ref path) = self.path {
-            fs::metadata(path).map_err(Into::into)
+            fs::metadata(path).context(Operation::Metadata, path)
+        } else if self.anonymous_dir.is_some() || self.memfd {
+            self.file()?
+                .metadata()
+                .context(Operation::Metadata, &self.context_path())
         } else {
             Err(Into::into(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -393,6 +854,25 @@ impl TempFile {
         }
     }
 
+    /// Disarms the `TempFile` and returns its path, file handle, first created parent directory, and registry entry, for wrapping in another type.
+    #[cfg(feature = "async")]
+    pub(crate) fn into_parts(
+        mut self,
+    ) -> (
+        Option<PathBuf>,
+        Option<File>,
+        Option<PathBuf>,
+        Option<usize>,
+    ) {
+        self.anonymous_dir = None;
+        (
+            self.path.take(),
+            self.file.take(),
+            self.created_parent.take(),
+            self.registry_id.take(),
+        )
+    }
+
     /// A function to convert a normal File and its path into a `TempFile`.
     ///
     /// # Errors
@@ -401,13 +881,37 @@ impl TempFile {
     #[cfg(unix)]
     pub fn from_fp<P: AsRef<Path>>(file: File, path: P) -> TempResult<Self> {
         if !Self::are_same_file(path.as_ref(), &file)? {
-            return Err(TempError::InvalidFileOrPath);
+            return Err(TempError::InvalidFileOrPath(path.as_ref().to_path_buf()));
         }
         Ok(Self {
             path: Some(path.as_ref().to_path_buf()),
             file: Some(file),
             created_parent: None,
-        })
+            anonymous_dir: None,
+            memfd: false,
+            quota: FileQuota::default(),
+            registry_id: None,
+        }
+        .registered())
+    }
+
+    /// Takes ownership of an existing file, opening it for reading and writing, or only reading if writing is not permitted.
+    pub(crate) fn open_existing(path: PathBuf) -> TempResult<Self> {
+        let file = match OpenOptions::new().read(true).write(true).open(&path) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => File::open(&path),
+            result => result,
+        }
+        .context(Operation::Open, &path)?;
+        Ok(Self {
+            path: Some(path),
+            file: Some(file),
+            created_parent: None,
+            anonymous_dir: None,
+            memfd: false,
+            quota: FileQuota::default(),
+            registry_id: None,
+        }
+        .registered())
     }
 
     /// Helper function to validate that a given &Path and File both point to the same file.
@@ -427,13 +931,522 @@ impl TempFile {
     }
 }
 
+#[cfg(unix)]
+impl TempFile {
+    /// Creates a new anonymous temporary file in the given directory.
+    ///
+    /// The file never has a visible name, so nothing is left behind even if the process is killed. On Linux it is opened with
+    /// `O_TMPFILE`; where the filesystem does not support that, a file is created and immediately unlinked instead.
+    /// Use [`link_into`](TempFile::link_into) to give it a name later.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The existing directory in which to create the file. If a relative directory is provided, it is resolved relative to the base directory of the current `TempConfig` (the system temporary directory by default).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created.
+    pub fn anonymous<P: AsRef<Path>>(dir: P) -> TempResult<Self> {
+        let dir_ref = normalize_path(dir.as_ref());
+        let dir_buf = if dir_ref.is_absolute() {
+            dir_ref
+        } else {
+            crate::config::base_dir()?.join(dir_ref)
+        };
+        let file = crate::helpers::open_unnamed(&dir_buf, TempConfig::current().file_mode)
+            .context(Operation::CreateFile, &dir_buf)?;
+        Ok(Self {
+            path: None,
+            file: Some(file),
+            created_parent: None,
+            anonymous_dir: Some(dir_buf),
+            memfd: false,
+            quota: FileQuota::default(),
+            registry_id: None,
+        })
+    }
+
+    /// Checks if the file is anonymous, i.e. has not been given a name yet.
+    #[must_use]
+    pub fn is_anonymous(&self) -> bool {
+        self.anonymous_dir.is_some()
+    }
+
+    /// Gives an anonymous temporary file a name.
+    ///
+    /// On Linux this links the `O_TMPFILE` inode into the filesystem with `linkat`, so the open handle keeps referring to it.
+    /// If that is not possible (the file was created with the unlink fallback, or `path` is on another filesystem), the
+    /// contents are copied into a new file at `path` and the handle is reopened there.
+    ///
+    /// Afterwards the file behaves like any other named `TempFile` and is deleted on drop unless persisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to give the file. If `path` is a bare file name, it is placed in the directory the file was created in.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::InvalidFileOrPath)` if the file is not anonymous, `Err(TempError::PathExists)` if `path` exists, or an error if linking or copying fails.
+    pub fn link_into<P: AsRef<Path>>(&mut self, path: P) -> TempResult<()> {
+        let dir = self.anonymous_dir.as_ref().ok_or_else(|| self.invalid())?;
+        let path = normalize_path(path.as_ref());
+        let path = if Self::is_bare_name(&path) {
+            dir.join(path)
+        } else {
+            path
+        };
+        self.link_at(path)
+    }
+
+    /// Gives a file without a path, such as an anonymous or memfd-backed one, the name `path`, which must not exist.
+    ///
+    /// The inode is linked with `linkat` where possible, and otherwise its contents are copied into a new file at `path`.
+    fn link_at(&mut self, path: PathBuf) -> TempResult<()> {
+        let file = self.file.as_mut().ok_or(TempError::FileIsNone)?;
+
+        #[cfg(target_os = "linux")]
+        let linked = match crate::helpers::link_fd(file, &path) {
+            Ok(()) => true,
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENOENT | libc::EXDEV)) => false,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(TempError::PathExists(path))
+            }
+            Err(e) => return Err(TempError::failed(Operation::CreateFile, &path, e)),
+        };
+        #[cfg(not(target_os = "linux"))]
+        let linked = false;
+
+        if !linked {
+            use std::os::unix::fs::OpenOptionsExt;
+            use std::os::unix::fs::PermissionsExt;
+            // A memfd is created with mode 0o777, so it gets the usual mode of new files instead.
+            let mode = if self.memfd {
+                TempConfig::current().file_mode
+            } else {
+                file.metadata()?.permissions().mode()
+            };
+            let mut dest = match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .mode(mode)
+                .open(&path)
+            {
+                Ok(dest) => dest,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    return Err(TempError::PathExists(path))
+                }
+                Err(e) => return Err(TempError::failed(Operation::CreateFile, &path, e)),
+            };
+            let pos = file.stream_position()?;
+            let result = file
+                .seek(SeekFrom::Start(0))
+                .and_then(|_| io::copy(file, &mut dest))
+                .and_then(|_| dest.seek(SeekFrom::Start(pos)));
+            if let Err(e) = result {
+                let _ = file.seek(SeekFrom::Start(pos));
+                let _ = fs::remove_file(&path);
+                return Err(TempError::failed(Operation::Copy, &path, e));
+            }
+            self.file = Some(dest);
+        }
+        self.anonymous_dir = None;
+        self.memfd = false;
+        self.set_path(path);
+        Ok(())
+    }
+
+    /// Moves a file without a path, such as an anonymous or memfd-backed one, to `path` by materializing it there.
+    ///
+    /// A bare file name is placed in the directory an anonymous file was created in, or in the base directory of the current
+    /// `TempConfig` for a memfd-backed one. With `PersistMode::Overwrite`, the file is materialized under a hidden name next to
+    /// `path` first and then renamed over it.
+    fn rename_unnamed(&mut self, path: PathBuf, mode: PersistMode) -> TempResult<()> {
+        if self.file.is_none() {
+            return Err(TempError::FileIsNone);
+        }
+        if self.anonymous_dir.is_none() && !self.memfd {
+            return Err(TempError::InvalidFileOrPath(path));
+        }
+        let path = if Self::is_bare_name(&path) {
+            match self.anonymous_dir {
+                Some(ref dir) => dir.join(path),
+                None => crate::config::base_dir()?.join(path),
+            }
+        } else {
+            path
+        };
+        if mode == PersistMode::NoClobber {
+            return self.link_at(path);
+        }
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let name = path
+            .file_name()
+            .ok_or_else(|| TempError::InvalidFileOrPath(path.clone()))?;
+        loop {
+            match self.link_at(crate::helpers::hidden_name(dir, name)) {
+                Err(TempError::PathExists(_)) => {}
+                result => break result,
+            }
+        }?;
+        self.move_to(path, PersistMode::Overwrite)
+    }
+
+    /// Takes an exclusive advisory lock on the file with `flock(2)`, blocking until it is available.
+    ///
+    /// The lock is released when the returned guard is dropped. Locks are advisory: they only exclude other lockers, not plain reads and writes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if locking fails.
+    pub fn lock_exclusive(&mut self) -> TempResult<TempFileLock<'_>> {
+        crate::lock::lock_fd(self.file()?.as_raw_fd(), true, true)?;
+        Ok(TempFileLock::new(self))
+    }
+
+    /// Takes a shared advisory lock on the file with `flock(2)`, blocking while an exclusive lock is held elsewhere.
+    ///
+    /// The lock is released when the returned guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if locking fails.
+    pub fn lock_shared(&mut self) -> TempResult<TempFileLock<'_>> {
+        crate::lock::lock_fd(self.file()?.as_raw_fd(), false, true)?;
+        Ok(TempFileLock::new(self))
+    }
+
+    /// Tries to take an exclusive advisory lock on the file without blocking.
+    ///
+    /// Returns `Ok(None)` if the file is already locked through another handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if locking fails.
+    pub fn try_lock(&mut self) -> TempResult<Option<TempFileLock<'_>>> {
+        if crate::lock::lock_fd(self.file()?.as_raw_fd(), true, false)? {
+            Ok(Some(TempFileLock::new(self)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Tries to take a shared advisory lock on the file without blocking.
+    ///
+    /// Returns `Ok(None)` if the file is exclusively locked through another handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if locking fails.
+    pub fn try_lock_shared(&mut self) -> TempResult<Option<TempFileLock<'_>>> {
+        if crate::lock::lock_fd(self.file()?.as_raw_fd(), false, false)? {
+            Ok(Some(TempFileLock::new(self)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Releases any advisory lock held through this file's handle, e.g. one whose guard was leaked.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if unlocking fails.
+    pub fn unlock(&self) -> TempResult<()> {
+        crate::lock::unlock_fd(self.file()?.as_raw_fd())?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl TempFile {
+    /// Creates a new temporary file backed by memory with `memfd_create`, which never exists on disk.
+    ///
+    /// The file supports sealing with [`seal`](TempFile::seal). It can be reopened by path through [`fd_path`](TempFile::fd_path),
+    /// and by child processes through [`fd_path_for_children`](TempFile::fd_path_for_children).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the file, used only for debugging (it appears in `/proc/self/fd`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` contains a NUL byte or if the file cannot be created.
+    pub fn memfd<S: AsRef<str>>(name: S) -> TempResult<Self> {
+        use std::os::fd::FromRawFd;
+        let name = std::ffi::CString::new(name.as_ref())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: `name` is a valid NUL-terminated string which outlives the call.
+        let fd = unsafe {
+            libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING)
+        };
+        if fd < 0 {
+            return Err(TempError::failed(
+                Operation::CreateFile,
+                Path::new(&format!("memfd:{}", name.to_string_lossy())),
+                io::Error::last_os_error(),
+            ));
+        }
+        // SAFETY: `fd` was just returned by `memfd_create` and is owned by nothing else.
+        let file = unsafe { File::from_raw_fd(fd) };
+        Ok(Self {
+            path: None,
+            file: Some(file),
+            created_parent: None,
+            anonymous_dir: None,
+            memfd: true,
+            quota: FileQuota::default(),
+            registry_id: None,
+        })
+    }
+
+    /// Checks if the file is backed by a memfd.
+    #[must_use]
+    pub fn is_memfd(&self) -> bool {
+        self.memfd
+    }
+
+    /// Adds seals to a memfd-backed file. Seals cannot be removed again.
+    ///
+    /// `SealFlags::WRITE` fails while a writable shared map of the file exists, e.g. a `MappedTempFile` from `map_mut`.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The seals to add.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::InvalidFileOrPath)` if the file is not a memfd, `Err(TempError::FileIsNone)` if the file
+    /// handle is not available, or an error if sealing fails (e.g. `EPERM` once `SealFlags::SEAL` is set).
+    pub fn seal(&self, flags: SealFlags) -> TempResult<()> {
+        if !self.memfd {
+            return Err(self.invalid());
+        }
+        // SAFETY: `F_ADD_SEALS` takes an integer argument and has no memory safety requirements.
+        if unsafe { libc::fcntl(self.file()?.as_raw_fd(), libc::F_ADD_SEALS, flags.0) } != 0 {
+            return Err(TempError::failed(
+                Operation::Seal,
+                &self.context_path(),
+                io::Error::last_os_error(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the seals currently applied to a memfd-backed file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::InvalidFileOrPath)` if the file is not a memfd, `Err(TempError::FileIsNone)` if the file
+    /// handle is not available, or an error if the seals cannot be read.
+    pub fn seals(&self) -> TempResult<SealFlags> {
+        if !self.memfd {
+            return Err(self.invalid());
+        }
+        // SAFETY: `F_GET_SEALS` takes no argument and has no memory safety requirements.
+        let seals = unsafe { libc::fcntl(self.file()?.as_raw_fd(), libc::F_GET_SEALS) };
+        if seals < 0 {
+            return Err(TempError::failed(
+                Operation::Metadata,
+                &self.context_path(),
+                io::Error::last_os_error(),
+            ));
+        }
+        Ok(SealFlags(seals))
+    }
+
+    /// Returns a path through which this process can open a memfd-backed file while the handle is open.
+    ///
+    /// The path is of the form `/proc/self/fd/{fd}`, so it only refers to the file within this process. Returns `None` if the
+    /// file is not a memfd or its handle is not available.
+    #[must_use]
+    pub fn fd_path(&self) -> Option<PathBuf> {
+        if !self.memfd {
+            return None;
+        }
+        let file = self.file.as_ref()?;
+        Some(PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd())))
+    }
+
+    /// Makes the handle of a memfd-backed file inheritable and returns a path through which child processes can open it.
+    ///
+    /// The memfd is created close-on-exec; this clears the flag, so every child spawned afterwards inherits the handle until
+    /// the file is closed. The path is of the form `/proc/{pid}/fd/{fd}` with the pid of this process, so it refers to the
+    /// file in any process which can read this process's `/proc` entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::InvalidFileOrPath)` if the file is not a memfd, `Err(TempError::FileIsNone)` if the file
+    /// handle is not available, or an error if the close-on-exec flag cannot be cleared.
+    pub fn fd_path_for_children(&self) -> TempResult<PathBuf> {
+        if !self.memfd {
+            return Err(self.invalid());
+        }
+        let fd = self.file()?.as_raw_fd();
+        // SAFETY: `F_GETFD` takes no argument and has no memory safety requirements.
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        // SAFETY: `F_SETFD` takes an integer argument and has no memory safety requirements.
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) } < 0 {
+            return Err(TempError::failed(
+                Operation::SetFdFlags,
+                &self.context_path(),
+                io::Error::last_os_error(),
+            ));
+        }
+        Ok(PathBuf::from(format!(
+            "/proc/{}/fd/{fd}",
+            std::process::id()
+        )))
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl TempFile {
+    /// Reads from the file starting at `offset`, without moving the file cursor. Returns the number of bytes read.
+    ///
+    /// On Windows the cursor is moved, as positional reads there always update it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if reading fails.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> TempResult<usize> {
+        let file = self.file()?;
+        #[cfg(unix)]
+        let n = std::os::unix::fs::FileExt::read_at(file, buf, offset)?;
+        #[cfg(windows)]
+        let n = std::os::windows::fs::FileExt::seek_read(file, buf, offset)?;
+        Ok(n)
+    }
+
+    /// Writes to the file starting at `offset`, without moving the file cursor. Returns the number of bytes written.
+    ///
+    /// On Windows the cursor is moved, as positional writes there always update it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, `Err(TempError::QuotaExceeded)` if the
+    /// write would grow the file past its quota, or an error if writing fails.
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> TempResult<usize> {
+        let file = self.file()?;
+        if self.quota.is_active() {
+            let end = offset.saturating_add(buf.len() as u64);
+            self.quota.reserve(end.max(file.metadata()?.len()))?;
+        }
+        #[cfg(unix)]
+        let result = std::os::unix::fs::FileExt::write_at(file, buf, offset);
+        #[cfg(windows)]
+        let result = std::os::windows::fs::FileExt::seek_write(file, buf, offset);
+        self.settle_quota();
+        Ok(result?)
+    }
+
+    /// Reads exactly `buf.len()` bytes from the file starting at `offset`, without moving the file cursor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, an error of kind `UnexpectedEof` if the file
+    /// ends before `buf` is filled, or an error if reading fails.
+    pub fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> TempResult<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    )
+                    .into())
+                }
+                Ok(n) => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+                Err(TempError::IO(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes all of `buf` to the file starting at `offset`, without moving the file cursor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if writing fails.
+    pub fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> TempResult<()> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    )
+                    .into())
+                }
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(TempError::IO(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the whole file from the start, without moving the file cursor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(TempError::FileIsNone)` if the file handle is not available, or an error if reading fails.
+    pub fn read_all(&self) -> TempResult<Vec<u8>> {
+        let mut contents = Vec::new();
+        let mut chunk = [0u8; 8192];
+        loop {
+            match self.read_at(&mut chunk, contents.len() as u64) {
+                Ok(0) => return Ok(contents),
+                Ok(n) => contents.extend_from_slice(&chunk[..n]),
+                Err(TempError::IO(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
 #[cfg(feature = "mmap_support")]
 impl TempFile {
+    /// Maps the file read-only. The file stays mutably borrowed until the returned map is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file handle is not available or if mapping the file fails.
+    pub fn map(&mut self) -> TempResult<MappedTempFile<'_>> {
+        MappedTempFile::new(self, MapMode::ReadOnly)
+    }
+
+    /// Maps the file read-write, so writes to the map reach the file. The file stays mutably borrowed until the returned map is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file handle is not available, is read-only, or if mapping the file fails.
+    pub fn map_mut(&mut self) -> TempResult<MappedTempFile<'_>> {
+        MappedTempFile::new(self, MapMode::ReadWrite)
+    }
+
+    /// Maps the file copy-on-write, so writes to the map stay private to it. The file stays mutably borrowed until the returned map is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file handle is not available or if mapping the file fails.
+    pub fn map_copy(&mut self) -> TempResult<MappedTempFile<'_>> {
+        MappedTempFile::new(self, MapMode::CopyOnWrite)
+    }
+
     /// Creates a read-only memory map of the file.
     ///
+    /// Prefer [`map`](TempFile::map), whose borrow keeps the file from changing while it is mapped.
+    ///
     /// # Safety
     ///
-    /// This operation is unsafe because it relies on the underlying file not changing unexpectedly.
+    /// The file must not be truncated or otherwise modified while the map exists.
     ///
     /// # Errors
     ///
@@ -445,37 +1458,28 @@ impl TempFile {
 
     /// Creates a mutable memory map of the file.
     ///
+    /// Prefer [`map_mut`](TempFile::map_mut), whose borrow keeps the file from changing while it is mapped.
+    ///
     /// # Safety
     ///
-    /// This operation is unsafe because it allows mutable access to the file's contents.
+    /// The file must not be truncated or otherwise modified, other than through the map, while the map exists.
     ///
     /// # Errors
     ///
     /// Returns an error if mapping the file fails.
     pub unsafe fn mmap_mut(&mut self) -> TempResult<MmapMut> {
-        let file = self.file_mut()?;
-        unsafe {
-            MmapOptions::new()
-                .map_mut(Self::immut(file))
-                .map_err(Into::into)
-        }
-    }
-
-    // Workaround for memmap2 API quirks... but seriously, why does it work like this?
-    /// Converts a mutable file reference into a static immutable reference for use with memory mapping.
-    ///
-    /// # Safety
-    ///
-    /// This function intentionally leaks the provided file reference to extend its lifetime, satisfying the API requirements of the memory mapping library.
-    fn immut(file: &mut File) -> &File {
-        Box::leak(Box::new(file))
+        let file = self.file()?;
+        unsafe { MmapOptions::new().map_mut(file).map_err(Into::into) }
     }
 }
 
 impl Write for TempFile {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_quota(buf.len())?;
         if let Some(ref mut file) = self.file {
-            file.write(buf)
+            let result = file.write(buf);
+            self.settle_quota();
+            result
         } else {
             Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -484,8 +1488,11 @@ impl Write for TempFile {
         }
     }
     fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.check_quota(bufs.iter().map(|b| b.len()).sum())?;
         if let Some(ref mut file) = self.file {
-            file.write_vectored(bufs)
+            let result = file.write_vectored(bufs);
+            self.settle_quota();
+            result
         } else {
             Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -563,14 +1570,10 @@ impl Seek for TempFile {
 
 impl Drop for TempFile {
     fn drop(&mut self) {
-        match (self.path.take(), self.created_parent.take()) {
-            (Some(p), None) => {
-                let _ = fs::remove_file(p);
-            }
-            (Some(_), Some(d)) => {
-                let _ = fs::remove_dir_all(d);
-            }
-            _ => {}
+        // Close the handle first; some platforms refuse to remove open files.
+        self.file = None;
+        if let Err((path, e)) = self.remove_now() {
+            crate::cleanup::report_drop_failure(&path, &e);
         }
     }
 }
@@ -631,12 +1634,8 @@ impl Display for TempFile {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self.file {
             None => writeln!(f, "No file"),
-            Some(ref file) => {
-                let mut buf = Vec::new();
-                file.try_clone()
-                    .expect("Failed to get new file handle")
-                    .read_to_end(&mut buf)
-                    .expect("Failed to read from file");
+            Some(_) => {
+                let buf = self.read_all().map_err(|_| std::fmt::Error)?;
                 writeln!(f, "{}", sew::infallible::InfallibleString::from(buf))
             }
         }
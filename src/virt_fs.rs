@@ -6,6 +6,13 @@ use std::path::Path;
 use std::time::SystemTime;
 use std::{fs, str};
 
+/// Converts a file offset into an index into a file's content.
+fn offset_to_index(offset: u64) -> std::io::Result<usize> {
+    usize::try_from(offset).map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "Offset is out of range")
+    })
+}
+
 /// Splits a path string (e.g. "/a/b/c") into its non-empty components as string slices.
 fn get_components(path: &str) -> Vec<&str> {
     path.split('/').filter(|s| !s.is_empty()).collect()
@@ -323,6 +330,69 @@ impl VirtFile {
     pub fn content_mut(&mut self) -> &mut Vec<u8> {
         &mut self.content
     }
+
+    /// Returns a copy of the whole content of the file, without moving the cursor.
+    #[must_use]
+    pub fn read_all(&self) -> Vec<u8> {
+        self.content.clone()
+    }
+
+    /// Reads from the file starting at `offset`, without moving the cursor. Returns the number of bytes read, which is zero at or past the end.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `offset` does not fit in memory.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        let start = offset_to_index(offset)?;
+        if start >= self.content.len() {
+            return Ok(0);
+        }
+        let to_read = (self.content.len() - start).min(buf.len());
+        buf[..to_read].copy_from_slice(&self.content[start..start + to_read]);
+        Ok(to_read)
+    }
+
+    /// Reads exactly `buf.len()` bytes from the file starting at `offset`, without moving the cursor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `UnexpectedEof` if the file ends before `buf` is filled.
+    pub fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+        if self.read_at(buf, offset)? < buf.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes to the file starting at `offset`, without moving the cursor. Writing past the end pads the file with zeros.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `offset` does not fit in memory.
+    pub fn write_at(&mut self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+        let start = offset_to_index(offset)?;
+        let end = start.checked_add(buf.len()).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Write is out of range")
+        })?;
+        if end > self.content.len() {
+            self.content.resize(end, 0);
+        }
+        self.content[start..end].copy_from_slice(buf);
+        self.metadata.modified = SystemTime::now();
+        Ok(buf.len())
+    }
+
+    /// Writes all of `buf` to the file starting at `offset`, without moving the cursor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `offset` does not fit in memory.
+    pub fn write_all_at(&mut self, buf: &[u8], offset: u64) -> std::io::Result<()> {
+        self.write_at(buf, offset).map(|_| ())
+    }
 }
 
 impl Default for VirtFS {
@@ -0,0 +1,32 @@
+// The registry is process-wide, so these tests live in their own binary to avoid removing files other tests are using.
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use tempfs::registry::{cleanup_registered, enable_registry};
+    use tempfs::{TempDir, TempFile};
+
+    #[test]
+    fn test_registry_cleanup() {
+        enable_registry();
+        let dir_path = env::temp_dir().join("test_registry_cleanup_dir");
+        let file_path = env::temp_dir().join("test_registry_cleanup_file.txt");
+        let persisted_path = env::temp_dir().join("test_registry_cleanup_persisted.txt");
+
+        let mut temp_dir = TempDir::new(&dir_path).expect("Failed to create TempDir");
+        temp_dir.create_file("inner.txt").expect("Failed to create file");
+        let temp_file = TempFile::new(&file_path).expect("Failed to create TempFile");
+        let mut persisted = TempFile::new(&persisted_path).expect("Failed to create TempFile");
+        drop(persisted.persist().expect("Persist failed"));
+
+        // Simulate a process which is interrupted before any destructor runs.
+        std::mem::forget(temp_dir);
+        std::mem::forget(temp_file);
+        let report = cleanup_registered();
+        assert!(report.is_clean(), "{report}");
+        assert!(!dir_path.exists());
+        assert!(!file_path.exists());
+        assert!(persisted_path.exists());
+        fs::remove_file(&persisted_path).expect("Failed to remove persisted file");
+    }
+}
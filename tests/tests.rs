@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod tests {
+    use tempfs::builder::{TempDirBuilder, TempFileBuilder};
+    use tempfs::spooled_temp_file::SpooledTempFile;
     use tempfs::temp_dir::TempDir;
-    use tempfs::temp_file::TempFile;
+    use tempfs::temp_file::{PersistMode, TempFile};
     use std::env;
     use std::fs;
     use std::io::{Read, Seek, SeekFrom, Write};
@@ -48,6 +50,84 @@ mod tests {
         fs::remove_file(&new_path).expect("Failed to remove renamed file");
     }
 
+    #[test]
+    fn test_temp_file_persist_to_no_clobber() {
+        let temp_path = env::temp_dir().join("test_temp_file_persist_to.txt");
+        let target = env::temp_dir().join("test_temp_file_persist_to_target.txt");
+        fs::write(&target, b"existing").expect("Failed to create target");
+        {
+            let mut temp_file = TempFile::new(&temp_path).expect("Failed to create TempFile");
+            temp_file.write_all(b"new").expect("Failed to write data");
+            assert!(temp_file.persist_to(&target, PersistMode::NoClobber).is_err());
+            assert_eq!(fs::read(&target).unwrap(), b"existing");
+            assert_eq!(temp_file.path().unwrap(), temp_path.as_path());
+
+            fs::remove_file(&target).expect("Failed to remove target");
+            let mut file = temp_file
+                .persist_to(&target, PersistMode::NoClobber)
+                .expect("Persist failed");
+            // The handle still refers to the renamed inode.
+            file.write_all(b" data").expect("Failed to write data");
+        }
+        assert!(!temp_path.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"new data");
+        fs::remove_file(&target).expect("Failed to remove persisted file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_file_anonymous_link_into() {
+        let temp_dir = TempDir::new(env::temp_dir().join("test_temp_file_anonymous")).expect("Failed to create TempDir");
+        let dir_path = temp_dir.path().unwrap().to_path_buf();
+        let linked = dir_path.join("linked.txt");
+        {
+            let mut temp_file = TempFile::anonymous(&dir_path).expect("Failed to create anonymous TempFile");
+            assert!(temp_file.is_anonymous());
+            assert!(temp_file.is_active());
+            temp_file.write_all(b"unnamed").expect("Failed to write data");
+            assert_eq!(fs::read_dir(&dir_path).unwrap().count(), 0);
+
+            temp_file.link_into("linked.txt").expect("Failed to link file");
+            assert!(!temp_file.is_anonymous());
+            assert_eq!(temp_file.path().unwrap(), linked.as_path());
+            temp_file.write_all(b" then named").expect("Failed to write data");
+            assert_eq!(fs::read(&linked).unwrap(), b"unnamed then named");
+        }
+        assert!(!linked.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_anonymous_persist_materializes() {
+        let temp_dir = TempDir::new(env::temp_dir().join("test_anonymous_persist")).expect("Failed to create TempDir");
+        let dir_path = temp_dir.path().unwrap().to_path_buf();
+        let target = dir_path.join("target.txt");
+
+        let mut first = TempFile::anonymous(&dir_path).expect("Failed to create anonymous TempFile");
+        first.write_all(b"first").expect("Failed to write data");
+        first.persist_to(&target, PersistMode::NoClobber).expect("Failed to persist file");
+        assert_eq!(fs::read(&target).unwrap(), b"first");
+
+        let mut second = TempFile::anonymous(&dir_path).expect("Failed to create anonymous TempFile");
+        second.write_all(b"second").expect("Failed to write data");
+        assert!(matches!(
+            second.persist_to(&target, PersistMode::NoClobber),
+            Err(tempfs::TempError::PathExists(_))
+        ));
+        second.persist_durable(&target, PersistMode::Overwrite).expect("Failed to persist file");
+        assert_eq!(fs::read(&target).unwrap(), b"second");
+        assert_eq!(fs::read_dir(&dir_path).unwrap().count(), 1);
+        assert!(matches!(second.rename("again.txt"), Err(tempfs::TempError::FileIsNone)));
+
+        #[cfg(target_os = "linux")]
+        {
+            let mut memfd = TempFile::memfd("blob").expect("Failed to create memfd");
+            memfd.write_all(b"from memory").expect("Failed to write data");
+            memfd.persist_to(dir_path.join("memfd.txt"), PersistMode::NoClobber).expect("Failed to persist memfd");
+            assert_eq!(fs::read(dir_path.join("memfd.txt")).unwrap(), b"from memory");
+        }
+    }
+
     #[test]
     fn test_temp_dir_create_and_file() {
         let temp_dir_path = env::temp_dir().join("test_temp_dir");
@@ -103,4 +183,780 @@ mod tests {
         assert!(!temp_dir_path.exists());
         assert!(!temp_dir_path.exists());
     }
+
+    #[test]
+    fn test_builders_name_and_mode() {
+        let temp_dir = TempDirBuilder::new()
+            .prefix("build-")
+            .rand_len(6)
+            .build()
+            .expect("Failed to build TempDir");
+        let dir_path = temp_dir.path().unwrap().to_path_buf();
+        let dir_name = dir_path.file_name().unwrap().to_str().unwrap();
+        assert!(dir_name.starts_with("build-"));
+        assert_eq!(dir_name.len(), "build-".len() + 6);
+        {
+            let temp_file = TempFileBuilder::new()
+                .prefix("upload-")
+                .suffix(".part")
+                .mode(0o600)
+                .dir(&dir_path)
+                .build()
+                .expect("Failed to build TempFile");
+            let file_path = temp_file.path().unwrap();
+            let file_name = file_path.file_name().unwrap().to_str().unwrap();
+            assert_eq!(file_path.parent().unwrap(), dir_path.as_path());
+            assert!(file_name.starts_with("upload-") && file_name.ends_with(".part"));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(temp_file.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+            }
+
+            let fixed = TempFileBuilder::new()
+                .prefix("fixed")
+                .rand_len(0)
+                .dir(&dir_path)
+                .build()
+                .expect("Failed to build TempFile");
+            assert!(fixed.path().unwrap().ends_with("fixed"));
+            assert!(TempFileBuilder::new().prefix("fixed").rand_len(0).dir(&dir_path).build().is_err());
+        }
+        drop(temp_dir);
+        assert!(!dir_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_file_modes() {
+        use std::os::unix::fs::PermissionsExt;
+        let default_path = env::temp_dir().join("test_temp_file_mode_default.txt");
+        let script_path = env::temp_dir().join("test_temp_file_mode_script.sh");
+        let default_file = TempFile::new(&default_path).expect("Failed to create TempFile");
+        let mode = default_file.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0, "temporary files must not be executable by default");
+        assert_eq!(mode & 0o600, 0o600);
+
+        let script = TempFile::new_with_mode(&script_path, 0o700).expect("Failed to create TempFile");
+        assert_eq!(script.metadata().unwrap().permissions().mode() & 0o700, 0o700);
+    }
+
+    #[test]
+    fn test_spooled_temp_file_rollover() {
+        let mut spooled = SpooledTempFile::new(8);
+        spooled.write_all(b"small").expect("Failed to write data");
+        assert!(!spooled.is_rolled());
+        assert_eq!(spooled.in_memory(), Some(&b"small"[..]));
+
+        spooled.write_all(b" but growing").expect("Failed to write data");
+        assert!(spooled.is_rolled());
+        assert_eq!(spooled.in_memory(), None);
+
+        spooled.seek(SeekFrom::Start(0)).expect("Failed to seek");
+        let mut content = String::new();
+        spooled.read_to_string(&mut content).expect("Failed to read data");
+        assert_eq!(content, "small but growing");
+
+        let mut explicit = SpooledTempFile::new(1024);
+        explicit.write_all(b"abc").expect("Failed to write data");
+        explicit.rollover().expect("Failed to roll over");
+        assert!(explicit.is_rolled());
+        let mut file = explicit.into_temp_file().expect("Failed to get TempFile");
+        file.seek(SeekFrom::Start(0)).expect("Failed to seek");
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).expect("Failed to read data");
+        assert_eq!(content, b"abc");
+
+        let mut missing = SpooledTempFile::new_in(2, env::temp_dir().join("test_spooled_missing_dir"));
+        let err = missing.write_all(b"abc").expect_err("Rollover into a missing directory should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_temp_file_persist_and_cleanup() {
+        use tempfs::async_temp_file::AsyncTempFile;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+        let temp_dir = TempDir::new(env::temp_dir().join("test_async_temp_file")).expect("Failed to create TempDir");
+        let mut temp_file = temp_dir.create_file_async("async.txt").await.expect("Failed to create AsyncTempFile");
+        temp_file.write_all(b"async data").await.expect("Failed to write data");
+        temp_file.seek(SeekFrom::Start(0)).await.expect("Failed to seek");
+        let mut content = String::new();
+        temp_file.read_to_string(&mut content).await.expect("Failed to read data");
+        assert_eq!(content, "async data");
+
+        let target = temp_dir.path().unwrap().join("persisted.txt");
+        temp_file.persist_to(&target, PersistMode::NoClobber).await.expect("Persist failed");
+        assert_eq!(fs::read(&target).unwrap(), b"async data");
+
+        let other = AsyncTempFile::new(temp_dir.path().unwrap().join("other.txt")).await.expect("Failed to create AsyncTempFile");
+        let other_path = other.path().unwrap().to_path_buf();
+        other.cleanup().await.expect("Cleanup failed");
+        assert!(!other_path.exists());
+
+        let dir_path = temp_dir.path().unwrap().to_path_buf();
+        let report = temp_dir.cleanup_async().await.expect("Directory cleanup failed");
+        assert!(report.is_clean());
+        assert!(!dir_path.exists());
+    }
+
+    #[test]
+    fn test_temp_file_persist_durable() {
+        let temp_dir = TempDir::new(env::temp_dir().join("test_temp_file_persist_durable")).expect("Failed to create TempDir");
+        let target = temp_dir.path().unwrap().join("snapshot.bin");
+        let mut temp_file = TempFile::new(temp_dir.path().unwrap().join("snapshot.tmp")).expect("Failed to create TempFile");
+        temp_file.write_all(b"snapshot").expect("Failed to write data");
+        drop(temp_file.persist_durable(&target, PersistMode::Overwrite).expect("Durable persist failed"));
+        assert!(!temp_file.is_active());
+        assert_eq!(fs::read(&target).unwrap(), b"snapshot");
+
+        let mut missing = TempFile::new(temp_dir.path().unwrap().join("other.tmp")).expect("Failed to create TempFile");
+        let err = missing
+            .persist_durable(temp_dir.path().unwrap().join("no/such/dir/file"), PersistMode::Overwrite)
+            .expect_err("Persist into a missing directory should fail");
+        assert!(matches!(err, tempfs::TempError::Persist { step: tempfs::PersistStep::Rename, .. }));
+    }
+
+    #[test]
+    fn test_persist_durable_error_has_path() {
+        let temp_dir = TempDir::new(env::temp_dir().join("test_persist_durable_error_has_path")).expect("Failed to create TempDir");
+        let blocker = temp_dir.path().unwrap().join("blocker");
+        fs::write(&blocker, b"not a directory").unwrap();
+        let target = blocker.join("file.bin");
+        let mut temp_file = TempFile::new(temp_dir.path().unwrap().join("file.tmp")).expect("Failed to create TempFile");
+        let err = temp_file
+            .persist_durable(&target, PersistMode::Overwrite)
+            .expect_err("Persist below a file should fail");
+        assert_eq!(err.path(), Some(target.as_path()));
+        assert!(err.to_string().contains(&target.display().to_string()));
+        assert!(temp_file.is_active());
+    }
+
+    #[test]
+    fn test_explicit_cleanup() {
+        let temp_dir_path = env::temp_dir().join("test_explicit_cleanup");
+        let mut temp_dir = TempDir::new(&temp_dir_path).expect("Failed to create TempDir");
+        temp_dir.create_file("tracked.txt").expect("Failed to create file");
+        fs::create_dir_all(temp_dir_path.join("nested/deeper")).expect("Failed to create subdirectories");
+        fs::write(temp_dir_path.join("nested/deeper/untracked.txt"), b"x").expect("Failed to write file");
+
+        let file_path = temp_dir_path.join("standalone.txt");
+        let temp_file = TempFile::new(&file_path).expect("Failed to create TempFile");
+        temp_file.cleanup().expect("File cleanup failed");
+        assert!(!file_path.exists());
+
+        let report = temp_dir.cleanup().expect("Directory cleanup failed");
+        assert!(report.is_clean(), "{report}");
+        assert!(!temp_dir_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reaper_removes_orphans() {
+        let base = TempDir::new(env::temp_dir().join("test_reaper_removes_orphans")).expect("Failed to create TempDir");
+        let base_path = base.path().unwrap();
+        let mut child = std::process::Command::new("true").spawn().expect("Failed to spawn child");
+        let dead_pid = child.id();
+        child.wait().expect("Failed to wait for child");
+
+        let orphan = base_path.join("orphan");
+        fs::create_dir(&orphan).expect("Failed to create directory");
+        fs::write(orphan.join(tempfs::reaper::OWNER_MARKER), format!("{dead_pid}\n")).expect("Failed to write marker");
+        let staging = base_path.join(format!("{}result.{dead_pid}-0.tmp", tempfs::reaper::STAGING_PREFIX));
+        fs::write(&staging, b"partial").expect("Failed to write staging file");
+        let lookalike = base_path.join(format!(".notes.{dead_pid}-0.tmp"));
+        fs::write(&lookalike, b"user data").expect("Failed to write file");
+        let unrelated = base_path.join("unrelated");
+        fs::create_dir(&unrelated).expect("Failed to create directory");
+        let live = TempDirBuilder::new().dir(base_path).owner_marker(true).build().expect("Failed to build TempDir");
+        let unmarked = TempDir::new(base_path.join("unmarked")).expect("Failed to create TempDir").keep().unwrap();
+
+        let dry = tempfs::Reaper::new(base_path).dry_run(true).reap().expect("Dry run failed");
+        assert_eq!(dry.reaped().len(), 2);
+        assert!(orphan.exists() && staging.exists());
+
+        let report = tempfs::Reaper::new(base_path).reap().expect("Reap failed");
+        assert!(report.failures().is_clean());
+        assert!(report.reaped().iter().all(|e| e.reason == tempfs::reaper::ReapReason::OwnerGone));
+        assert!(!orphan.exists() && !staging.exists());
+        assert!(unrelated.exists() && lookalike.exists() && live.path().unwrap().exists());
+
+        let aged = tempfs::Reaper::new(base_path).ttl(std::time::Duration::ZERO).reap().expect("Reap failed");
+        assert!(aged.reaped().iter().all(|e| !e.path.starts_with(&unmarked)));
+        assert!(unmarked.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_advisory_locks() {
+        let mut temp_dir = TempDir::new(env::temp_dir().join("test_advisory_locks")).expect("Failed to create TempDir");
+        let file_path = temp_dir.path().unwrap().join("data.bin");
+        let mut temp_file = TempFile::new(&file_path).expect("Failed to create TempFile");
+        let other = fs::File::open(&file_path).expect("Failed to open file");
+        {
+            let mut guard = temp_file.lock_exclusive().expect("Failed to lock file");
+            guard.write_all(b"locked").expect("Failed to write data");
+            assert!(other.try_lock_shared().is_err());
+        }
+        other.try_lock_shared().expect("Lock should be released on drop");
+        assert!(temp_file.try_lock().expect("Failed to try lock").is_none());
+        other.unlock().expect("Failed to unlock");
+        let shared = temp_file.try_lock_shared().expect("Failed to try lock").expect("Lock should be free");
+        shared.unlock().expect("Failed to unlock");
+
+        let lock = temp_dir.lockfile("worker.lock").expect("Failed to take lock file");
+        assert!(temp_dir.get_file("worker.lock").is_some());
+        let contender = fs::File::open(lock.path()).expect("Failed to open lock file");
+        assert!(contender.try_lock().is_err());
+        drop(lock);
+        contender.try_lock().expect("Lock file should be released on drop");
+
+        temp_dir.set_quota(Some(4));
+        drop(temp_dir.lockfile("quota.lock").expect("Failed to take lock file"));
+        let lock_file = temp_dir.get_file_mut("quota.lock").expect("Lock file should be tracked");
+        assert!(matches!(lock_file.set_len(8), Err(tempfs::TempError::QuotaExceeded { limit: 4, .. })));
+    }
+
+    #[cfg(feature = "mmap_support")]
+    #[test]
+    fn test_mapped_temp_file() {
+        let mut temp_file = TempFile::new(env::temp_dir().join("test_mapped_temp_file.bin")).expect("Failed to create TempFile");
+        temp_file.write_all(b"hello").expect("Failed to write data");
+        {
+            let mut map = temp_file.map_mut().expect("Failed to map file");
+            map.as_mut_slice().unwrap()[0] = b'j';
+            map.grow(8).expect("Failed to grow map");
+            assert_eq!(map.len(), 8);
+            map.as_mut_slice().unwrap()[5..].copy_from_slice(b"!!!");
+            map.flush_range(0, 8).expect("Failed to flush map");
+            assert!(map.flush_range(4, 8).is_err());
+        }
+        {
+            let mut copy = temp_file.map_copy().expect("Failed to map file");
+            copy.as_mut_slice().unwrap()[0] = b'x';
+            copy.grow(10).expect("Failed to grow map");
+            assert_eq!(&copy[..8], b"xello!!!");
+        }
+        let map = temp_file.map().expect("Failed to map file");
+        assert_eq!(&map[..8], b"jello!!!");
+        assert_eq!(map.len(), 10);
+    }
+
+    #[test]
+    fn test_positional_io() {
+        let mut temp_file = TempFile::new(env::temp_dir().join("test_positional_io.bin")).expect("Failed to create TempFile");
+        temp_file.write_all(b"0123456789").expect("Failed to write data");
+        temp_file.write_all_at(b"ab", 2).expect("Failed to write at offset");
+        let mut buf = [0u8; 3];
+        temp_file.read_exact_at(&mut buf, 1).expect("Failed to read at offset");
+        assert_eq!(&buf, b"1ab");
+        assert!(temp_file.read_exact_at(&mut buf, 9).is_err());
+        assert_eq!(temp_file.stream_position().unwrap(), 10);
+        assert_eq!(temp_file.read_all().unwrap(), b"01ab456789");
+        assert_eq!(temp_file.stream_position().unwrap(), 10);
+    }
+
+    #[cfg(feature = "virt_fs")]
+    #[test]
+    fn test_virt_file_positional_io() {
+        let mut file = tempfs::VirtFile::new("/pos.bin", tempfs::VirtMetadata::new(0o644));
+        file.write_all(b"hello").expect("Failed to write data");
+        file.write_all_at(b"!", 7).expect("Failed to write at offset");
+        let mut buf = [0u8; 2];
+        file.read_exact_at(&mut buf, 3).expect("Failed to read at offset");
+        assert_eq!(&buf, b"lo");
+        assert_eq!(file.read_all(), b"hello\0\0!");
+        assert_eq!(file.stream_position().unwrap(), 5);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_memfd_sealing() {
+        use tempfs::temp_file::SealFlags;
+        let mut temp_file = TempFile::memfd("blob").expect("Failed to create memfd");
+        assert!(temp_file.is_memfd() && temp_file.is_active());
+        temp_file.write_all(b"read-only blob").expect("Failed to write data");
+        temp_file.seal(SealFlags::IMMUTABLE).expect("Failed to seal memfd");
+        assert!(temp_file.seals().unwrap().contains(SealFlags::WRITE | SealFlags::GROW));
+        assert!(temp_file.write_all(b"more").is_err());
+        assert!(temp_file.set_len(0).is_err());
+        let err = temp_file.seal(SealFlags::WRITE).expect_err("Sealing after SEAL should fail");
+        assert_eq!(err.operation(), Some(tempfs::error::Operation::Seal));
+        assert!(err.path().unwrap().starts_with("/proc/self/fd"));
+
+        let path = temp_file.fd_path_for_children().expect("memfd should have an fd path");
+        let output = std::process::Command::new("cat").arg(&path).output().expect("Failed to run cat");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"read-only blob");
+        let own = temp_file.fd_path().expect("memfd should have an fd path");
+        assert_eq!(fs::read(&own).expect("Failed to read through fd path"), b"read-only blob");
+        assert_eq!(temp_file.metadata().unwrap().len(), 14);
+        assert!(TempFile::new(env::temp_dir().join("test_memfd_sealing")).unwrap().seal(SealFlags::SEAL).is_err());
+    }
+
+    #[test]
+    fn test_temp_locations() {
+        let system = tempfs::TempLocation::SystemTemp.resolve().expect("Failed to resolve location");
+        assert_eq!(system, env::temp_dir());
+        let temp_dir = TempDir::new_at(&tempfs::TempLocation::SystemTemp, "test_temp_locations").expect("Failed to create TempDir");
+        assert_eq!(temp_dir.path().unwrap(), env::temp_dir().join("test_temp_locations"));
+
+        let largest = tempfs::TempLocation::LargestFree { candidates: vec![env::temp_dir(), "/no/such/dir".into()], min_free: 0 };
+        let temp_file = TempFileBuilder::new().location(largest).build().expect("Failed to build TempFile");
+        assert!(temp_file.path().unwrap().starts_with(env::temp_dir()));
+        let too_big = tempfs::TempLocation::LargestFree { candidates: vec![env::temp_dir()], min_free: u64::MAX };
+        assert!(TempDirBuilder::new().location(too_big).build().is_err());
+
+        #[cfg(target_os = "linux")]
+        if let Ok(ram) = tempfs::TempLocation::RamBacked.resolve() {
+            let temp_file = TempFile::new_at(&tempfs::TempLocation::RamBacked, "test_temp_locations.bin").expect("Failed to create TempFile");
+            assert!(temp_file.path().unwrap().starts_with(ram));
+        }
+    }
+
+    #[test]
+    fn test_quotas() {
+        let mut temp_file = TempFile::new(env::temp_dir().join("test_quotas.bin")).expect("Failed to create TempFile");
+        temp_file.set_quota(Some(8));
+        temp_file.write_all(b"12345678").expect("Write within quota failed");
+        let err = temp_file.write_all(b"9").expect_err("Write past quota should fail");
+        assert!(matches!(tempfs::TempError::from(err), tempfs::TempError::QuotaExceeded { limit: 8, attempted: 9 }));
+        temp_file.seek(SeekFrom::Start(0)).unwrap();
+        temp_file.write_all(b"abc").expect("Overwrite within quota failed");
+        assert!(matches!(temp_file.set_len(16), Err(tempfs::TempError::QuotaExceeded { .. })));
+
+        let mut temp_dir = TempDir::new(env::temp_dir().join("test_quotas")).expect("Failed to create TempDir");
+        temp_dir.create_file("a.bin").unwrap().write_all(&[0; 6]).expect("Failed to write data");
+        temp_dir.set_quota(Some(10));
+        assert_eq!(temp_dir.quota_used(), 6);
+        let b = temp_dir.create_file("b.bin").expect("Failed to create file");
+        b.write_all(&[0; 4]).expect("Write within quota failed");
+        assert!(b.write_all(&[0; 1]).is_err());
+        b.set_len(1).expect("Failed to shrink file");
+        assert_eq!(temp_dir.quota_used(), 7);
+        temp_dir.remove_file("a.bin");
+        assert_eq!(temp_dir.quota_used(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_quota_released_after_failed_write() {
+        let mut temp_dir = TempDir::new(env::temp_dir().join("test_quota_released_after_failed_write")).expect("Failed to create TempDir");
+        temp_dir.set_quota(Some(u64::MAX));
+        let file = temp_dir.create_file("a.bin").expect("Failed to create file");
+        file.write_all(b"abc").expect("Failed to write data");
+        // An offset past `i64::MAX` passes the quota check but is rejected by `pwrite`.
+        assert!(file.write_at(b"x", 1 << 63).is_err());
+        assert!(file.set_len(1 << 63).is_err());
+        assert_eq!(temp_dir.quota_used(), 3);
+        temp_dir.set_quota(Some(4));
+        let file = temp_dir.get_file_mut("a.bin").unwrap();
+        file.write_all(b"d").expect("Write within quota failed after a failed write");
+        assert_eq!(temp_dir.quota_used(), 4);
+    }
+
+    #[test]
+    fn test_nested_dirs() {
+        let root_path = env::temp_dir().join("test_nested_dirs");
+        let mut temp_dir = TempDir::new(&root_path).expect("Failed to create TempDir");
+        temp_dir.create_file("top.txt").expect("Failed to create file");
+        let sub = temp_dir.create_dir("sub").expect("Failed to create subdirectory");
+        sub.create_file("inner.txt").expect("Failed to create file");
+        assert!(temp_dir.create_dir("sub").is_err());
+        temp_dir.create_file_at("a/b/c.txt").expect("Failed to create nested file");
+        temp_dir.create_file_at("a/d.txt").expect("Failed to create nested file");
+        assert!(temp_dir.create_file_at("../escape.txt").is_err());
+
+        let tree = temp_dir.list_tree().expect("Directory should have a path");
+        assert_eq!(tree.files, vec![root_path.join("top.txt").as_path()]);
+        assert_eq!(tree.dirs.len(), 2);
+        let a = &tree.dirs[1];
+        assert_eq!(a.path, root_path.join("a"));
+        assert_eq!(a.files, vec![root_path.join("a/d.txt").as_path()]);
+        assert_eq!(a.dirs[0].files, vec![root_path.join("a/b/c.txt").as_path()]);
+        assert!(temp_dir.get_dir("a").unwrap().get_dir("b").is_some());
+
+        let report = temp_dir.cleanup().expect("Cleanup failed");
+        assert!(report.is_clean(), "{report}");
+        assert!(!root_path.exists());
+    }
+
+    #[test]
+    fn test_rescan_and_adopt() {
+        let root_path = env::temp_dir().join("test_rescan_and_adopt");
+        let mut temp_dir = TempDir::new(&root_path).expect("Failed to create TempDir");
+        temp_dir.create_file("tracked.txt").expect("Failed to create file");
+        fs::write(root_path.join("output.o"), b"obj").expect("Failed to write file");
+        fs::create_dir_all(root_path.join("gen/deep")).expect("Failed to create directories");
+        fs::write(root_path.join("gen/deep/out.rs"), b"fn main() {}").expect("Failed to write file");
+
+        let entries = temp_dir.entries().expect("Failed to list entries");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.iter().filter(|e| e.tracked).count(), 1);
+
+        fs::write(root_path.join("moved.bin"), b"moved").expect("Failed to write file");
+        let moved = temp_dir.adopt("moved.bin").expect("Failed to adopt file");
+        assert_eq!(moved.read_all().unwrap(), b"moved");
+        assert!(temp_dir.adopt(env::temp_dir().join("elsewhere.bin")).is_err());
+
+        assert_eq!(temp_dir.rescan().expect("Rescan failed"), 4);
+        assert_eq!(temp_dir.rescan().expect("Rescan failed"), 0);
+        assert!(temp_dir.get_file("output.o").is_some());
+        assert!(temp_dir.get_dir("gen").unwrap().get_dir("deep").unwrap().get_file("out.rs").is_some());
+        assert!(temp_dir.entries().unwrap().iter().all(|e| e.tracked));
+        drop(temp_dir);
+        assert!(!root_path.exists());
+    }
+
+    #[test]
+    fn test_path_traversal_rejected() {
+        use tempfs::TempError;
+        let root_path = env::temp_dir().join("test_path_traversal");
+        let outside = env::temp_dir().join("test_path_traversal_outside");
+        fs::create_dir_all(&outside).expect("Failed to create directory");
+        let mut temp_dir = TempDir::new(&root_path).expect("Failed to create TempDir");
+        assert!(matches!(
+            temp_dir.create_file("../../etc/x"),
+            Err(TempError::EscapesDirectory(_))
+        ));
+        assert!(matches!(
+            temp_dir.create_file("/etc/x"),
+            Err(TempError::EscapesDirectory(_))
+        ));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, root_path.join("link"))
+                .expect("Failed to create symlink");
+            assert!(matches!(
+                temp_dir.create_file("link/x"),
+                Err(TempError::EscapesDirectory(_))
+            ));
+            assert!(temp_dir.create_file_at("link/x").is_err());
+            assert!(!outside.join("x").exists());
+        }
+        temp_dir.create_file("a/../b.txt").expect("Failed to create file");
+        temp_dir.create_file("sub/c.txt").expect("Failed to create file");
+        assert!(temp_dir.get_file("b.txt").is_some());
+        assert!(temp_dir.get_file("sub/c.txt").is_some());
+        assert!(temp_dir.get_file("../test_path_traversal/b.txt").is_none());
+        drop(temp_dir);
+        assert!(!root_path.exists());
+        assert!(outside.exists());
+        fs::remove_dir(&outside).expect("Failed to remove directory");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_cleanup_does_not_follow_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let root_path = env::temp_dir().join("test_symlink_safe_cleanup");
+        let outside = env::temp_dir().join("test_symlink_safe_cleanup_home");
+        fs::create_dir_all(&outside).expect("Failed to create directory");
+        fs::write(outside.join("keep.txt"), b"keep").expect("Failed to write file");
+        {
+            let mut temp_dir = TempDir::new(&root_path).expect("Failed to create TempDir");
+            temp_dir.create_file("a.txt").expect("Failed to create file");
+            std::os::unix::fs::symlink(&outside, root_path.join("home"))
+                .expect("Failed to create symlink");
+            let locked = root_path.join("locked");
+            fs::create_dir(&locked).expect("Failed to create directory");
+            fs::write(locked.join("b.txt"), b"b").expect("Failed to write file");
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o500))
+                .expect("Failed to set permissions");
+        }
+        assert!(!root_path.exists());
+        assert!(outside.join("keep.txt").exists());
+
+        // Only parent directories which are left empty are removed.
+        let parent = env::temp_dir().join("test_symlink_safe_cleanup_parent");
+        let file = TempFile::new(parent.join("nested/file.txt")).expect("Failed to create TempFile");
+        fs::write(parent.join("other.txt"), b"other").expect("Failed to write file");
+        drop(file);
+        assert!(!parent.join("nested").exists());
+        assert!(parent.join("other.txt").exists());
+        fs::remove_dir_all(&parent).expect("Failed to remove directory");
+        fs::remove_dir_all(&outside).expect("Failed to remove directory");
+    }
+
+    #[test]
+    fn test_keep_preserves_contents() {
+        let root_path = env::temp_dir().join("test_keep_preserves_contents");
+        let mut temp_dir = TempDir::new(&root_path).expect("Failed to create TempDir");
+        temp_dir.create_file("a.txt").expect("Failed to create file");
+        temp_dir.create_file_at("sub/b.txt").expect("Failed to create nested file");
+        let kept = temp_dir.into_path().expect("Directory should have a path");
+        assert!(kept.join("a.txt").exists());
+        assert!(kept.join("sub/b.txt").exists());
+        fs::remove_dir_all(&kept).expect("Failed to remove directory");
+
+        let panic_path = env::temp_dir().join("test_keep_on_panic");
+        let result = std::panic::catch_unwind(|| {
+            let mut temp_dir = TempDir::new(&panic_path).expect("Failed to create TempDir");
+            temp_dir.keep_on_panic();
+            temp_dir.create_file("log.txt").expect("Failed to create file");
+            panic!("Simulated test failure");
+        });
+        assert!(result.is_err());
+        assert!(panic_path.join("log.txt").exists());
+        fs::remove_dir_all(&panic_path).expect("Failed to remove directory");
+
+        let mut temp_dir = TempDir::new(&panic_path).expect("Failed to create TempDir");
+        temp_dir.keep_on_panic();
+        drop(temp_dir);
+        assert!(!panic_path.exists());
+    }
+
+    #[test]
+    fn test_builder_keep_overrides_config() {
+        use tempfs::config::{CleanupPolicy, TempConfig};
+        let base = env::temp_dir().join("test_builder_keep_overrides_config");
+        let _base_dir = TempDir::new(&base).expect("Failed to create TempDir");
+        let mut config = TempConfig::new();
+        config.cleanup_policy(CleanupPolicy {
+            keep_on_panic: true,
+            keep_if_env: false,
+        });
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _temp_dir = TempDirBuilder::new()
+                .config(config.clone())
+                .dir(&base)
+                .keep_on_panic(false)
+                .build()
+                .expect("Failed to build TempDir");
+            panic!("Simulated test failure");
+        }));
+        assert!(result.is_err());
+        assert_eq!(fs::read_dir(&base).unwrap().count(), 0);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _temp_dir = TempDirBuilder::new()
+                .config(config.clone())
+                .dir(&base)
+                .build()
+                .expect("Failed to build TempDir");
+            panic!("Simulated test failure");
+        }));
+        assert!(result.is_err());
+        assert_eq!(fs::read_dir(&base).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_name_generators() {
+        use tempfs::name_gen::{NameGenerator, SeededNames, SequentialNames, TimestampNames, UuidNames};
+        let a = SeededNames::new(42);
+        let b = SeededNames::new(42);
+        let first = a.generate(12);
+        assert_eq!(first.len(), 12);
+        assert_eq!(first, b.generate(12));
+        assert_ne!(a.generate(12), first);
+
+        let uuid = UuidNames::v4().generate(0);
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_eq!(&UuidNames::v7().generate(0)[14..15], "7");
+        let stamps = TimestampNames::new();
+        assert!(stamps.generate(0) < stamps.generate(0));
+
+        let dir_path = env::temp_dir().join("test_name_generators");
+        let mut temp_dir = TempDir::new(&dir_path).expect("Failed to create TempDir");
+        let names = SequentialNames::new();
+        fs::write(dir_path.join(format!("{:016}", 0)), b"taken").expect("Failed to write file");
+        let file = temp_dir
+            .create_random_file_with(&names)
+            .expect("Failed to create file");
+        assert_eq!(file.path().unwrap(), dir_path.join(format!("{:016}", 1)));
+        let sub = TempDir::new_random_with(Some(&dir_path), &SeededNames::new(7))
+            .expect("Failed to create TempDir");
+        let again = SeededNames::new(7).generate(16);
+        assert_eq!(sub.path().unwrap(), dir_path.join(again));
+
+        let built = TempFileBuilder::new()
+            .dir(&dir_path)
+            .prefix("snap-")
+            .rand_len(3)
+            .name_generator(SequentialNames::starting_at(7))
+            .build()
+            .expect("Failed to build TempFile");
+        assert_eq!(built.path().unwrap(), dir_path.join("snap-007"));
+    }
+
+    #[test]
+    fn test_scoped_config() {
+        use tempfs::config::TempConfig;
+        use tempfs::name_gen::SequentialNames;
+        let base = env::temp_dir().join("test_scoped_config");
+        let _base_dir = TempDir::new(&base).expect("Failed to create TempDir");
+        let mut config = TempConfig::new();
+        config
+            .base_dir(&base)
+            .name_generator(SequentialNames::new())
+            .name_len(4)
+            .retries(2);
+
+        {
+            let _guard = config.scoped();
+            let file = TempFile::new("relative.txt").expect("Failed to create TempFile");
+            assert_eq!(file.path().unwrap(), base.join("relative.txt"));
+            let dir = TempDir::new_random_with(None::<&str>, &SequentialNames::new())
+                .expect("Failed to create TempDir");
+            assert_eq!(dir.path().unwrap(), base.join("0000"));
+            fs::create_dir(base.join("0001")).expect("Failed to create directory");
+            fs::create_dir(base.join("0002")).expect("Failed to create directory");
+            assert!(TempDir::new_random_with(None::<&str>, &SequentialNames::starting_at(1)).is_err());
+        }
+
+        let outside = TempFile::new("test_scoped_config.txt").expect("Failed to create TempFile");
+        assert_eq!(outside.path().unwrap(), env::temp_dir().join("test_scoped_config.txt"));
+
+        let built = TempFileBuilder::new()
+            .config(config.clone())
+            .prefix("cfg-")
+            .build()
+            .expect("Failed to build TempFile");
+        assert_eq!(built.path().unwrap(), base.join("cfg-0000"));
+        let built_dir = TempDirBuilder::new()
+            .config(config)
+            .prefix("cfg-")
+            .build()
+            .expect("Failed to build TempDir");
+        assert_eq!(built_dir.path().unwrap(), base.join("cfg-0001"));
+    }
+
+    #[test]
+    fn test_name_space_exhausted() {
+        use tempfs::config::TempConfig;
+        use tempfs::error::TempError;
+        use tempfs::name_gen::NameGenerator;
+
+        #[derive(Debug)]
+        struct Fixed;
+        impl NameGenerator for Fixed {
+            fn generate(&self, _len: usize) -> String {
+                "taken".to_string()
+            }
+        }
+
+        let dir_path = env::temp_dir().join("test_name_space_exhausted");
+        let _temp_dir = TempDir::new(&dir_path).expect("Failed to create TempDir");
+        fs::create_dir(dir_path.join("taken")).expect("Failed to create directory");
+
+        match TempDir::new_random_with(Some(&dir_path), &Fixed) {
+            Err(TempError::NameSpaceExhausted { attempts, dir }) => {
+                assert_eq!(attempts, 1024);
+                assert_eq!(dir, dir_path);
+            }
+            other => panic!("Expected NameSpaceExhausted, got {other:?}"),
+        }
+
+        let mut config = TempConfig::new();
+        config.retries(3);
+        let _guard = config.scoped();
+        match TempFile::new_random_with(Some(&dir_path), &Fixed) {
+            Err(TempError::NameSpaceExhausted { attempts, .. }) => assert_eq!(attempts, 3),
+            other => panic!("Expected NameSpaceExhausted, got {other:?}"),
+        }
+        assert!(dir_path.join("taken").is_dir());
+        drop(_guard);
+
+        let mut config = TempConfig::new();
+        config.retries(0);
+        let _guard = config.scoped();
+        let file = TempFile::new_random_with(Some(&dir_path), &tempfs::name_gen::SequentialNames::new())
+            .expect("Zero retries should still try one name");
+        assert!(file.path().unwrap().is_file());
+        match TempFile::new_random_with(Some(&dir_path), &Fixed) {
+            Err(TempError::NameSpaceExhausted { attempts, .. }) => assert_eq!(attempts, 1),
+            other => panic!("Expected NameSpaceExhausted, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_error_context() {
+        use std::error::Error;
+        use tempfs::error::{Operation, TempError};
+        let dir_path = env::temp_dir().join("test_error_context");
+        let mut temp_dir = TempDir::new(&dir_path).expect("Failed to create TempDir");
+        let blocker = dir_path.join("blocker");
+        fs::write(&blocker, b"not a directory").expect("Failed to write file");
+
+        let err = TempFile::new(blocker.join("sub").join("file.txt")).unwrap_err();
+        assert_eq!(err.operation(), Some(Operation::CreateParent));
+        assert_eq!(err.path(), Some(blocker.join("sub").as_path()));
+        assert!(err.source().is_some());
+        assert!(err.to_string().contains("creating parent directories"));
+        assert!(err.to_string().contains(&blocker.display().to_string()));
+
+        let kind = err.kind();
+        let io_err: std::io::Error = err.into();
+        assert_eq!(io_err.kind(), kind);
+        let back = io_err.downcast::<TempError>().expect("Failed to recover TempError");
+        assert_eq!(back.operation(), Some(Operation::CreateParent));
+
+        let err = temp_dir.create_file_at("").unwrap_err();
+        assert!(matches!(err, TempError::InvalidFileOrPath(_)));
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let io_err: std::io::Error = TempError::PathExists(blocker.clone()).into();
+        assert_eq!(io_err.kind(), std::io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_quota_covers_existing_subdirectories() {
+        let dir_path = env::temp_dir().join("test_quota_covers_existing_subdirectories");
+        let mut temp_dir = TempDir::new(&dir_path).expect("Failed to create TempDir");
+        temp_dir.create_dir("sub").expect("Failed to create subdirectory");
+        temp_dir
+            .create_file_at("sub/before.bin")
+            .expect("Failed to create file")
+            .write_all(b"12")
+            .expect("Write within quota failed");
+        temp_dir.set_quota(Some(4));
+        assert_eq!(temp_dir.quota_used(), 2);
+
+        let file = temp_dir.create_file_at("sub/x.bin").expect("Failed to create file");
+        assert!(file.write_all(b"0123456789").is_err());
+        file.write_all(b"ab").expect("Write within quota failed");
+        assert_eq!(temp_dir.quota_used(), 4);
+
+        let sub = temp_dir.get_dir_mut("sub").expect("Failed to get subdirectory");
+        sub.set_quota(Some(100));
+        assert_eq!(sub.quota(), Some(100));
+        assert!(sub
+            .create_file("y.bin")
+            .expect("Failed to create file")
+            .write_all(b"z")
+            .is_err());
+        assert_eq!(temp_dir.quota(), Some(4));
+    }
+
+    #[test]
+    fn test_rescan_skips_nested_tracked_files() {
+        fn count(tree: &tempfs::DirTree<'_>, path: &std::path::Path) -> usize {
+            tree.files.iter().filter(|f| **f == path).count()
+                + tree.dirs.iter().map(|d| count(d, path)).sum::<usize>()
+        }
+        let root_path = env::temp_dir().join("test_rescan_skips_nested_tracked_files");
+        let mut temp_dir = TempDir::new(&root_path).expect("Failed to create TempDir");
+        temp_dir.set_quota(Some(100));
+        temp_dir
+            .create_file("a/b.txt")
+            .expect("Failed to create file")
+            .write_all(b"0123456789")
+            .expect("Failed to write file");
+        temp_dir.create_dir("c/d").expect("Failed to create subdirectory");
+        fs::write(root_path.join("c/d/e.txt"), b"e").expect("Failed to write file");
+
+        assert_eq!(temp_dir.rescan().expect("Rescan failed"), 3);
+        assert_eq!(temp_dir.rescan().expect("Rescan failed"), 0);
+        assert_eq!(temp_dir.quota_used(), 11);
+        let nested = root_path.join("a/b.txt");
+        temp_dir.adopt("a/b.txt").expect("Failed to adopt file");
+        assert_eq!(temp_dir.quota_used(), 11);
+        let tree = temp_dir.list_tree().expect("Failed to list tree");
+        assert_eq!(count(&tree, &nested), 1);
+        assert_eq!(count(&tree, &root_path.join("c/d/e.txt")), 1);
+        drop(temp_dir);
+        assert!(!root_path.exists());
+    }
 }